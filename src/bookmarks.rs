@@ -0,0 +1,164 @@
+use crate::session;
+use crate::worker::Job;
+use crate::ColoringMode;
+use egui_extras::RetainedImage;
+use std::path::{Path, PathBuf};
+
+// a named view, kept as the same fields an exported image carries
+// stored as csv with a header row, so the list can go through a spreadsheet and back
+pub struct Bookmark {
+    pub name: String,
+    pub fields: Vec<(String, String)>,
+    // rendered in the background when first shown
+    pub thumbnail: Option<RetainedImage>,
+    // that render while it's under way, and the colouring to show it in
+    pub rendering: Option<(Job, ColoringMode)>,
+}
+
+// where the list is kept between runs
+pub fn saved() -> Option<PathBuf> {
+    return Some(session::config()?.join("bookmarks.csv"));
+}
+
+pub fn write(path: &Path, bookmarks: &[Bookmark]) -> Result<(), String> {
+    // every key any bookmark has, in the order they first turn up
+    let mut columns = vec![String::from("name")];
+    for bookmark in bookmarks {
+        for (key, _) in &bookmark.fields {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    let mut text = line(&columns);
+    for bookmark in bookmarks {
+        let row: Vec<String> = columns
+            .iter()
+            .map(|column| match column.as_str() {
+                "name" => bookmark.name.clone(),
+                _ => bookmark
+                    .fields
+                    .iter()
+                    .find(|(key, _)| key == column)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        text += &line(&row);
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+    }
+    return std::fs::write(path, text).map_err(|e| format!("can't write {}: {}", path.display(), e));
+}
+
+// empty cells are left out, whatever restores the view reports the ones it needed
+pub fn read(path: &Path) -> Result<Vec<Bookmark>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let mut rows = parse(&text).ok_or(format!("{} has an unclosed quote", path.display()))?.into_iter();
+    let columns = rows.next().unwrap_or_default();
+    let name = columns
+        .iter()
+        .position(|column| column.trim() == "name")
+        .ok_or(format!("{} has no name column", path.display()))?;
+    return Ok(rows
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .map(|row| Bookmark {
+            name: row.get(name).cloned().unwrap_or_default(),
+            fields: columns
+                .iter()
+                .zip(row)
+                .enumerate()
+                .filter(|(i, (_, cell))| *i != name && !cell.is_empty())
+                .map(|(_, (column, cell))| (column.trim().to_string(), cell))
+                .collect(),
+            thumbnail: None,
+            rendering: None,
+        })
+        .collect());
+}
+
+// one csv line, quoting the cells that need it
+fn line(cells: &[String]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect();
+    return cells.join(",") + "\n";
+}
+// rows of cells, quoted cells may hold commas, doubled quotes and line breaks
+fn parse(text: &str) -> Option<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut cell)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    return Some(rows);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempFile;
+
+    #[test]
+    fn csv_quoting() {
+        let row: Vec<String> = ["plain", "with, comma", "say \"hi\"", "two\r\nlines"].map(String::from).to_vec();
+        assert_eq!(line(&row), "plain,\"with, comma\",\"say \"\"hi\"\"\",\"two\r\nlines\"\n");
+        assert_eq!(parse(&line(&row)), Some(vec![row]));
+        // windows line endings and no newline at the end, as spreadsheets write them
+        assert_eq!(parse("a,b\r\nc,d"), Some(vec![vec!["a", "b"], vec!["c", "d"]].into_iter().map(|row| row.into_iter().map(String::from).collect()).collect()));
+        assert_eq!(parse("name\n\"unclosed\n"), None);
+    }
+
+    #[test]
+    fn columns() {
+        let file = TempFile::new("bookmarks.csv");
+        let bookmark = |name: &str, fields: &[(&str, &str)]| Bookmark {
+            name: name.to_string(),
+            fields: fields.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            thumbnail: None,
+            rendering: None,
+        };
+        // a column only the second one has is left empty for the first, and dropped reading it back
+        let list = [bookmark("one", &[("zoom", "1")]), bookmark("two", &[("step", "z^3 + c"), ("zoom", "2")])];
+        write(&file.0, &list).unwrap();
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "name,zoom,step\none,1,\ntwo,2,z^3 + c\n");
+        let back = read(&file.0).unwrap();
+        assert_eq!(back[0].fields, list[0].fields);
+        assert_eq!(back[1].name, "two");
+        std::fs::write(&file.0, "zoom\n1\n").unwrap();
+        assert!(read(&file.0).err().unwrap().contains("no name column"));
+    }
+}
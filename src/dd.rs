@@ -0,0 +1,135 @@
+use crate::precision::{BigCoord, Decimal};
+
+// double-double arithmetic: an unevaluated sum hi + lo of two f64s, ~32 significant digits
+// cheap enough to iterate directly for zooms that f64 can't resolve but don't need perturbation yet
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let v = s - a;
+    (s, (a - (s - v)) + (b - v))
+}
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+impl DoubleDouble {
+    pub fn from_f64(x: f64) -> DoubleDouble {
+        DoubleDouble { hi: x, lo: 0. }
+    }
+    pub fn from_decimal(x: &Decimal) -> DoubleDouble {
+        let hi = x.to_f64();
+        let lo = x.add(&Decimal::from_f64(-hi, 40)).to_f64();
+        let (hi, lo) = quick_two_sum(hi, lo);
+        DoubleDouble { hi, lo }
+    }
+    pub fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (hi, lo) = quick_two_sum(s, e + self.lo + other.lo);
+        DoubleDouble { hi, lo }
+    }
+    pub fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self.add(DoubleDouble {
+            hi: -other.hi,
+            lo: -other.lo,
+        })
+    }
+    pub fn add_f64(self, other: f64) -> DoubleDouble {
+        let (s, e) = two_sum(self.hi, other);
+        let (hi, lo) = quick_two_sum(s, e + self.lo);
+        DoubleDouble { hi, lo }
+    }
+    pub fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        // fused multiply-add gives the exact rounding error of hi * hi
+        let p = self.hi * other.hi;
+        let e = self.hi.mul_add(other.hi, -p);
+        let (hi, lo) = quick_two_sum(p, e + self.hi * other.lo + self.lo * other.hi);
+        DoubleDouble { hi, lo }
+    }
+    pub fn double(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: self.hi * 2.,
+            lo: self.lo * 2.,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DDComplex {
+    pub re: DoubleDouble,
+    pub im: DoubleDouble,
+}
+impl DDComplex {
+    pub fn from_f64(re: f64, im: f64) -> DDComplex {
+        DDComplex {
+            re: DoubleDouble::from_f64(re),
+            im: DoubleDouble::from_f64(im),
+        }
+    }
+    pub fn from_coord(center: &BigCoord) -> DDComplex {
+        DDComplex {
+            re: DoubleDouble::from_decimal(&center.x),
+            im: DoubleDouble::from_decimal(&center.y),
+        }
+    }
+    pub fn offset(self, dx: f64, dy: f64) -> DDComplex {
+        DDComplex {
+            re: self.re.add_f64(dx),
+            im: self.im.add_f64(dy),
+        }
+    }
+    fn add(self, other: DDComplex) -> DDComplex {
+        DDComplex {
+            re: self.re.add(other.re),
+            im: self.im.add(other.im),
+        }
+    }
+    fn mul(self, other: DDComplex) -> DDComplex {
+        DDComplex {
+            re: self.re.mul(other.re).sub(self.im.mul(other.im)),
+            im: self.re.mul(other.im).add(self.im.mul(other.re)),
+        }
+    }
+    fn square(self) -> DDComplex {
+        DDComplex {
+            re: self.re.mul(self.re).sub(self.im.mul(self.im)),
+            im: self.re.mul(self.im).double(),
+        }
+    }
+    fn powi(self, n: i32) -> DDComplex {
+        if n == 1 {
+            return self;
+        }
+        if n % 2 == 0 {
+            return self.square().powi(n / 2);
+        }
+        return self.mul(self.square().powi((n - 1) / 2));
+    }
+    fn norm(self) -> f64 {
+        self.re.hi * self.re.hi + self.im.hi * self.im.hi
+    }
+}
+
+// z^n + c in double-double, same conventions as mandelcomp
+pub fn ddcomp(mut z: DDComplex, c: DDComplex, maxitr: f64, n: i32) -> (i32, f64) {
+    let mut iterations: i32 = 0;
+    let mut zold = z;
+    while iterations < maxitr as i32 {
+        let r = z.norm();
+        if r >= 4. {
+            return (iterations, r);
+        }
+        z = z.powi(n).add(c);
+        if z == zold {
+            return (maxitr as i32, 0.);
+        }
+        if iterations % 25 == 0 {
+            zold = z;
+        }
+        iterations += 1;
+    }
+    return (iterations, z.norm());
+}
@@ -0,0 +1,262 @@
+use crate::formula::Formula;
+use crate::fractal::{render, Coord, Plane, Progress};
+use crate::simd::Simd;
+use crate::supersample::Supersampling;
+use crate::viewport::Viewport;
+use crate::ColoringMode;
+use eframe::egui;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// rows rendered at a time, so an 8k export never holds more than a strip of samples
+const BAND: i32 = 128;
+// posters are rendered in tiles this many pixels a side, each kept on disk until the end
+const TILE: i32 = 512;
+
+// a png being rendered and written on a background thread, dropping it cancels it
+// tiled (poster) exports keep every finished tile next to the png, so they can resume
+pub struct Export {
+    progress: Arc<Progress>,
+    receiver: Receiver<Result<String, String>>,
+    lines: usize,
+    tiles: Option<Tiles>,
+    started: Instant,
+}
+struct Tiles {
+    // including the ones found from an earlier run
+    done: Arc<AtomicUsize>,
+    total: usize,
+    resumed: usize,
+}
+impl Export {
+    pub fn spawn(
+        viewport: Viewport,
+        maxitr: f64,
+        formula: Box<dyn Formula>,
+        julia: Option<Coord>,
+        supersampling: Supersampling,
+        coloring: ColoringMode,
+        path: String,
+        // png text chunks, keyword and value
+        text: Vec<(String, String)>,
+        tiled: bool,
+        ctx: egui::Context,
+    ) -> Export {
+        let (width, height) = (viewport.width, viewport.height);
+        let passes = if supersampling.enabled() { 2 } else { 1 };
+        // every seam between bands is rendered from both sides
+        let seams = (height as usize).div_ceil(BAND as usize) - 1;
+        let lines = (height as usize + 2 * seams) * passes;
+        let cache = PathBuf::from(format!("{}.tiles", path));
+        let signature = format!("{}x{} in tiles of {}\n{:?}\n{:?}\n", width, height, TILE, supersampling, text);
+        let prepared = if tiled { prepare(&cache, &signature) } else { Ok(()) };
+        let tiles = tiled.then(|| {
+            let all = layout(width, height);
+            Tiles {
+                done: Arc::new(AtomicUsize::new(0)),
+                total: all.len(),
+                resumed: all.iter().filter(|(x, y, _, _)| tile_path(&cache, *x, *y).exists()).count(),
+            }
+        });
+        let done = tiles.as_ref().map(|tiles| {
+            tiles.done.store(tiles.resumed, Ordering::Relaxed);
+            tiles.done.clone()
+        });
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = channel();
+        let shared = progress.clone();
+        std::thread::spawn(move || {
+            // set up once for the whole image, at deep zoom that's a reference orbit every band shares
+            let whole = Plane::new(&viewport, maxitr, &*formula, julia, Simd::best(), &shared);
+            // the w x h pixels at (x, y), with a pixel of margin rendered and dropped wherever there's
+            // image, so adaptive supersampling sees the neighbours across the seam
+            let rect = |x: i32, y: i32, w: i32, h: i32| -> Result<Vec<u8>, String> {
+                let (left, top) = ((x > 0) as i32, (y > 0) as i32);
+                let (right, bottom) = ((x + w < width) as i32, (y + h < height) as i32);
+                let whole = whole.as_ref().ok_or("cancelled")?;
+                let plane = whole.crop(x - left, y - top, w + left + right, h + top + bottom);
+                let rgba = render(&plane, supersampling, coloring, &shared).ok_or("cancelled")?;
+                let stride = plane.width as usize * 4;
+                let (left, w) = (left as usize * 4, w as usize * 4);
+                return Ok(rgba
+                    .chunks(stride)
+                    .skip(top as usize)
+                    .take(h as usize)
+                    .flat_map(|row| &row[left..left + w])
+                    .copied()
+                    .collect());
+            };
+            let result = match done {
+                None => write(&path, width, height, &text, |writer| {
+                    for top in (0..height).step_by(BAND as usize) {
+                        let rgba = rect(0, top, width, BAND.min(height - top))?;
+                        writer.write_all(&rgba).map_err(|e| e.to_string())?;
+                    }
+                    return Ok(());
+                }),
+                Some(done) => prepared.and_then(|_| {
+                    for (x, y, w, h) in layout(width, height) {
+                        let file = tile_path(&cache, x, y);
+                        if file.exists() {
+                            continue;
+                        }
+                        let rgba = rect(x, y, w, h).map_err(|e| {
+                            let kept = done.load(Ordering::Relaxed);
+                            format!("{}, {} tiles kept in {} to resume from", e, kept, cache.display())
+                        })?;
+                        // renamed into place once complete, so a half written tile is never picked up
+                        let partial = file.with_extension("partial");
+                        std::fs::write(&partial, rgba).map_err(|e| e.to_string())?;
+                        std::fs::rename(&partial, &file).map_err(|e| e.to_string())?;
+                        done.fetch_add(1, Ordering::Relaxed);
+                        ctx.request_repaint();
+                    }
+                    write(&path, width, height, &text, |writer| assemble(&cache, width, height, writer))?;
+                    let _ = std::fs::remove_dir_all(&cache);
+                    return Ok(());
+                }),
+            };
+            let _ = sender.send(result.map(|_| path));
+            ctx.request_repaint();
+        });
+        Export {
+            progress,
+            receiver,
+            lines,
+            tiles,
+            started: Instant::now(),
+        }
+    }
+    pub fn progress(&self) -> f32 {
+        match &self.tiles {
+            Some(tiles) => return tiles.done.load(Ordering::Relaxed) as f32 / tiles.total as f32,
+            None => return self.progress.lines.load(Ordering::Relaxed) as f32 / self.lines as f32,
+        }
+    }
+    // for posters: tiles done out of how many, and roughly how long the rest will take from
+    // the pace of the tiles rendered since this run started (None until the first one)
+    pub fn tiles(&self) -> Option<(usize, usize, Option<Duration>)> {
+        let tiles = self.tiles.as_ref()?;
+        let done = tiles.done.load(Ordering::Relaxed);
+        let eta = (done > tiles.resumed).then(|| {
+            self.started.elapsed().mul_f64((tiles.total - done) as f64 / (done - tiles.resumed) as f64)
+        });
+        return Some((done, tiles.total, eta));
+    }
+    // the path written, or what went wrong, once it's over
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        self.receiver.try_recv().ok()
+    }
+}
+impl Drop for Export {
+    fn drop(&mut self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+// (x, y, width, height) of every tile, a row at a time
+fn layout(width: i32, height: i32) -> Vec<(i32, i32, i32, i32)> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(TILE as usize) {
+        for x in (0..width).step_by(TILE as usize) {
+            tiles.push((x, y, TILE.min(width - x), TILE.min(height - y)));
+        }
+    }
+    return tiles;
+}
+// raw rgba rows, no header
+fn tile_path(cache: &Path, x: i32, y: i32) -> PathBuf {
+    cache.join(format!("{}_{}.rgba", x, y))
+}
+// the cache directory, emptied if its tiles were rendered with anything different
+fn prepare(cache: &Path, signature: &str) -> Result<(), String> {
+    let file = cache.join("parameters.txt");
+    if std::fs::read_to_string(&file).ok().as_deref() != Some(signature) {
+        let _ = std::fs::remove_dir_all(cache);
+    }
+    std::fs::create_dir_all(cache).map_err(|e| format!("can't create {}: {}", cache.display(), e))?;
+    std::fs::write(&file, signature).map_err(|e| e.to_string())?;
+    return Ok(());
+}
+// the tiles stitched back together a line at a time, so the whole image is never in memory
+fn assemble(cache: &Path, width: i32, height: i32, writer: &mut dyn Write) -> Result<(), String> {
+    for y in (0..height).step_by(TILE as usize) {
+        let mut row: Vec<(BufReader<File>, usize)> = Vec::new();
+        for x in (0..width).step_by(TILE as usize) {
+            let file = File::open(tile_path(cache, x, y)).map_err(|e| e.to_string())?;
+            row.push((BufReader::new(file), TILE.min(width - x) as usize * 4));
+        }
+        let mut line = vec![0; width as usize * 4];
+        for _ in 0..TILE.min(height - y) {
+            let mut start = 0;
+            for (reader, bytes) in row.iter_mut() {
+                reader
+                    .read_exact(&mut line[start..start + *bytes])
+                    .map_err(|e| e.to_string())?;
+                start += *bytes;
+            }
+            writer.write_all(&line).map_err(|e| e.to_string())?;
+        }
+    }
+    return Ok(());
+}
+
+// an 8 bit rgba png with the given text chunks (utf-8 itxt, a formula can hold any character),
+// its rows (top to bottom) coming from rows
+fn write(
+    path: &str,
+    width: i32,
+    height: i32,
+    text: &[(String, String)],
+    rows: impl FnOnce(&mut dyn Write) -> Result<(), String>,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("can't create {}: {}", path, e))?;
+    let encode = || -> Result<(), String> {
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (keyword, value) in text {
+            encoder
+                .add_itxt_chunk(keyword.clone(), value.clone())
+                .map_err(|e| e.to_string())?;
+        }
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        let mut stream = writer.stream_writer().map_err(|e| e.to_string())?;
+        rows(&mut stream)?;
+        stream.finish().map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+        return Ok(());
+    };
+    let written = encode();
+    if written.is_err() {
+        // don't leave half an image behind, only ever the one created here though
+        let _ = std::fs::remove_file(path);
+    }
+    return written;
+}
+
+// every text chunk of a png, keyword and value, as export writes them
+pub fn read_text(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let file = File::open(path).map_err(|e| format!("can't open {}: {}", path.display(), e))?;
+    let reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|e| format!("{} isn't a readable png: {}", path.display(), e))?;
+    let info = reader.info();
+    let mut text: Vec<(String, String)> = info
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+        .collect();
+    for chunk in &info.compressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.get_text().map_err(|e| e.to_string())?));
+    }
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text().map_err(|e| e.to_string())?));
+    }
+    return Ok(text);
+}
@@ -0,0 +1,560 @@
+use crate::formula::Formula;
+use crate::fractal::{
+    cadd, ccos, ccosh, cdiv, cexp, clog, cmul, conj, csin, csinh, csub, norm, power, Complex,
+};
+use std::fmt;
+
+// user typed iteration formulas, e.g. "z^3 - z + c" or "sin(z)*c"
+// parsed into a tree, constant folded, then flattened into a small stack program
+
+const MAX_STACK: usize = 32;
+// brackets, signs and operators deep, checked while parsing so the recursion can't overflow
+const MAX_DEPTH: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+fn error<T>(position: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError {
+        position,
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Func {
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Tan,
+    Sinh,
+    Cosh,
+    Sqrt,
+    Conj,
+    Abs,
+    Re,
+    Im,
+}
+impl Func {
+    fn from_name(name: &str) -> Option<Func> {
+        match name {
+            "exp" => Some(Func::Exp),
+            "log" | "ln" => Some(Func::Log),
+            "sin" => Some(Func::Sin),
+            "cos" => Some(Func::Cos),
+            "tan" => Some(Func::Tan),
+            "sinh" => Some(Func::Sinh),
+            "cosh" => Some(Func::Cosh),
+            "sqrt" => Some(Func::Sqrt),
+            "conj" => Some(Func::Conj),
+            "abs" => Some(Func::Abs),
+            "re" => Some(Func::Re),
+            "im" => Some(Func::Im),
+            _ => None,
+        }
+    }
+    fn apply(&self, z: Complex) -> Complex {
+        match self {
+            Func::Exp => cexp(z),
+            Func::Log => clog(z),
+            Func::Sin => csin(z),
+            Func::Cos => ccos(z),
+            Func::Tan => cdiv(csin(z), ccos(z)),
+            Func::Sinh => csinh(z),
+            Func::Cosh => ccosh(z),
+            Func::Sqrt => power(z, Complex { re: 0.5, im: 0. }),
+            Func::Conj => conj(z),
+            Func::Abs => Complex {
+                re: norm(z).sqrt(),
+                im: 0.,
+            },
+            Func::Re => Complex { re: z.re, im: 0. },
+            Func::Im => Complex { re: z.im, im: 0. },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(Complex),
+    Z,
+    C,
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Func, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Imag(f64),
+    Name(String),
+    Op(char),
+    Open,
+    Close,
+    End,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        let start = i;
+        if ch.is_whitespace() {
+            i += 1;
+        } else if ch.is_ascii_digit() || ch == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // only treat e as an exponent when digits follow, so "2e" is still 2 * e
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = match text.parse::<f64>() {
+                Ok(value) => value,
+                Err(_) => return error(start, format!("bad number `{}`", text)),
+            };
+            let imaginary = i < chars.len()
+                && chars[i] == 'i'
+                && !(i + 1 < chars.len() && (chars[i + 1].is_alphanumeric() || chars[i + 1] == '_'));
+            if imaginary {
+                i += 1;
+                tokens.push((Token::Imag(value), start));
+            } else {
+                tokens.push((Token::Num(value), start));
+            }
+        } else if ch.is_alphabetic() || ch == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), start));
+        } else if "+-*/^".contains(ch) {
+            i += 1;
+            tokens.push((Token::Op(ch), start));
+        } else if ch == '(' {
+            i += 1;
+            tokens.push((Token::Open, start));
+        } else if ch == ')' {
+            i += 1;
+            tokens.push((Token::Close, start));
+        } else {
+            return error(start, format!("unexpected character `{}`", ch));
+        }
+    }
+    tokens.push((Token::End, chars.len()));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    depth: usize,
+}
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+    fn position(&self) -> usize {
+        self.tokens[self.next].1
+    }
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+    // one level deeper in the tree, both for the recursion here and for folding and flattening later
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return error(self.position(), "formula is nested too deeply");
+        }
+        Ok(())
+    }
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Expr, ParseError>) -> Result<Expr, ParseError> {
+        self.enter()?;
+        let expr = parse(self)?;
+        self.depth -= 1;
+        Ok(expr)
+    }
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        // every term nests the sum so far one deeper
+        let outer = self.depth;
+        let mut lhs = self.product()?;
+        loop {
+            match self.peek() {
+                Token::Op('+') => {
+                    self.advance();
+                    self.enter()?;
+                    lhs = Expr::Add(Box::new(lhs), Box::new(self.product()?));
+                }
+                Token::Op('-') => {
+                    self.advance();
+                    self.enter()?;
+                    lhs = Expr::Sub(Box::new(lhs), Box::new(self.product()?));
+                }
+                _ => {
+                    self.depth = outer;
+                    return Ok(lhs);
+                }
+            }
+        }
+    }
+    // product := unary (('*' | '/')? unary)*, so "2z" and "z c" multiply implicitly
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let outer = self.depth;
+        let mut lhs = self.unary()?;
+        loop {
+            match self.peek() {
+                Token::Op('*') => {
+                    self.advance();
+                    self.enter()?;
+                    lhs = Expr::Mul(Box::new(lhs), Box::new(self.unary()?));
+                }
+                Token::Op('/') => {
+                    self.advance();
+                    self.enter()?;
+                    lhs = Expr::Div(Box::new(lhs), Box::new(self.unary()?));
+                }
+                Token::Num(_) | Token::Imag(_) | Token::Name(_) | Token::Open => {
+                    self.enter()?;
+                    lhs = Expr::Mul(Box::new(lhs), Box::new(self.power()?));
+                }
+                _ => {
+                    self.depth = outer;
+                    return Ok(lhs);
+                }
+            }
+        }
+    }
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Token::Op('-') => {
+                self.advance();
+                Ok(Expr::Neg(Box::new(self.nested(Parser::unary)?)))
+            }
+            Token::Op('+') => {
+                self.advance();
+                self.nested(Parser::unary)
+            }
+            _ => self.power(),
+        }
+    }
+    // power := atom ('^' unary)?, right associative so z^2^3 is z^(2^3)
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.atom()?;
+        if let Token::Op('^') = self.peek() {
+            self.advance();
+            return Ok(Expr::Pow(Box::new(base), Box::new(self.nested(Parser::unary)?)));
+        }
+        Ok(base)
+    }
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        match self.advance() {
+            Token::Num(value) => Ok(Expr::Num(Complex { re: value, im: 0. })),
+            Token::Imag(value) => Ok(Expr::Num(Complex { re: 0., im: value })),
+            Token::Open => {
+                let inner = self.nested(Parser::sum)?;
+                match self.advance() {
+                    Token::Close => Ok(inner),
+                    _ => error(position, "unclosed bracket"),
+                }
+            }
+            Token::Name(name) => match name.as_str() {
+                "z" => Ok(Expr::Z),
+                "c" => Ok(Expr::C),
+                "i" => Ok(Expr::Num(Complex { re: 0., im: 1. })),
+                "pi" => Ok(Expr::Num(Complex {
+                    re: std::f64::consts::PI,
+                    im: 0.,
+                })),
+                "e" => Ok(Expr::Num(Complex {
+                    re: std::f64::consts::E,
+                    im: 0.,
+                })),
+                _ => match Func::from_name(&name) {
+                    Some(func) => {
+                        let open = self.position();
+                        if self.advance() != Token::Open {
+                            return error(open, format!("expected `(` after `{}`", name));
+                        }
+                        let argument = self.nested(Parser::sum)?;
+                        if self.advance() != Token::Close {
+                            return error(position, format!("unclosed bracket in `{}(`", name));
+                        }
+                        Ok(Expr::Call(func, Box::new(argument)))
+                    }
+                    None => error(position, format!("unknown name `{}`", name)),
+                },
+            },
+            Token::End => error(position, "unexpected end of formula"),
+            Token::Close => error(position, "unexpected `)`"),
+            Token::Op(op) => error(position, format!("unexpected `{}`", op)),
+        }
+    }
+}
+
+fn fold(expr: Expr) -> Expr {
+    let binary = |a: Expr, b: Expr, f: fn(Complex, Complex) -> Complex, make: fn(Box<Expr>, Box<Expr>) -> Expr| {
+        match (fold(a), fold(b)) {
+            (Expr::Num(a), Expr::Num(b)) => Expr::Num(f(a, b)),
+            (a, b) => make(Box::new(a), Box::new(b)),
+        }
+    };
+    match expr {
+        Expr::Neg(a) => match fold(*a) {
+            Expr::Num(a) => Expr::Num(Complex { re: -a.re, im: -a.im }),
+            a => Expr::Neg(Box::new(a)),
+        },
+        Expr::Add(a, b) => binary(*a, *b, cadd, Expr::Add),
+        Expr::Sub(a, b) => binary(*a, *b, csub, Expr::Sub),
+        Expr::Mul(a, b) => binary(*a, *b, cmul, Expr::Mul),
+        Expr::Div(a, b) => binary(*a, *b, cdiv, Expr::Div),
+        Expr::Pow(a, b) => binary(*a, *b, power, Expr::Pow),
+        Expr::Call(func, a) => match fold(*a) {
+            Expr::Num(a) => Expr::Num(func.apply(a)),
+            a => Expr::Call(func, Box::new(a)),
+        },
+        leaf => leaf,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Op {
+    Push(Complex),
+    Z,
+    C,
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Call(Func),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    ops: Vec<Op>,
+}
+impl Program {
+    pub fn compile(source: &str) -> Result<Program, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+            depth: 0,
+        };
+        let expr = parser.sum()?;
+        if *parser.peek() != Token::End {
+            return error(parser.position(), "expected an operator");
+        }
+        let mut ops = Vec::new();
+        let depth = flatten(&fold(expr), &mut ops);
+        if depth > MAX_STACK {
+            return error(0, "formula is nested too deeply");
+        }
+        Ok(Program { ops })
+    }
+    pub fn eval(&self, z: Complex, c: Complex) -> Complex {
+        let mut stack = [Complex { re: 0., im: 0. }; MAX_STACK];
+        let mut top = 0;
+        for op in &self.ops {
+            match *op {
+                Op::Push(value) => {
+                    stack[top] = value;
+                    top += 1;
+                }
+                Op::Z => {
+                    stack[top] = z;
+                    top += 1;
+                }
+                Op::C => {
+                    stack[top] = c;
+                    top += 1;
+                }
+                Op::Neg => {
+                    let a = stack[top - 1];
+                    stack[top - 1] = Complex { re: -a.re, im: -a.im };
+                }
+                Op::Call(func) => stack[top - 1] = func.apply(stack[top - 1]),
+                binary => {
+                    top -= 1;
+                    let (a, b) = (stack[top - 1], stack[top]);
+                    stack[top - 1] = match binary {
+                        Op::Add => cadd(a, b),
+                        Op::Sub => csub(a, b),
+                        Op::Mul => cmul(a, b),
+                        Op::Div => cdiv(a, b),
+                        _ => power(a, b),
+                    };
+                }
+            }
+        }
+        stack[0]
+    }
+}
+// postfix order, returns the stack depth needed
+fn flatten(expr: &Expr, ops: &mut Vec<Op>) -> usize {
+    let binary = |a: &Expr, b: &Expr, op: Op, ops: &mut Vec<Op>| {
+        let left = flatten(a, ops);
+        let right = flatten(b, ops);
+        ops.push(op);
+        left.max(right + 1)
+    };
+    match expr {
+        Expr::Num(value) => {
+            ops.push(Op::Push(*value));
+            1
+        }
+        Expr::Z => {
+            ops.push(Op::Z);
+            1
+        }
+        Expr::C => {
+            ops.push(Op::C);
+            1
+        }
+        Expr::Neg(a) => {
+            let depth = flatten(a, ops);
+            ops.push(Op::Neg);
+            depth
+        }
+        Expr::Call(func, a) => {
+            let depth = flatten(a, ops);
+            ops.push(Op::Call(*func));
+            depth
+        }
+        Expr::Add(a, b) => binary(a, b, Op::Add, ops),
+        Expr::Sub(a, b) => binary(a, b, Op::Sub, ops),
+        Expr::Mul(a, b) => binary(a, b, Op::Mul, ops),
+        Expr::Div(a, b) => binary(a, b, Op::Div, ops),
+        Expr::Pow(a, b) => binary(a, b, Op::Pow, ops),
+    }
+}
+
+// a compiled iteration plus the expression (in c) its orbit starts from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprFormula {
+    pub step: Program,
+    pub start: Program,
+    pub bailout: f64,
+}
+impl Formula for ExprFormula {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        self.step.eval(z, c)
+    }
+    fn critical(&self, c: Complex) -> Complex {
+        self.start.eval(Complex { re: 0., im: 0. }, c)
+    }
+    fn bailout(&self) -> f64 {
+        self.bailout * self.bailout
+    }
+    fn escaped(&self, z: Complex, bailout: f64) -> Option<f64> {
+        let r = norm(z);
+        // transcendental formulas overflow to inf/NaN instead of crossing the radius
+        if r >= bailout || r.is_nan() {
+            return Some(r.min(f64::MAX));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Z: Complex = Complex { re: 0.3, im: -0.7 };
+    const C: Complex = Complex { re: -1.1, im: 0.4 };
+
+    fn eval(source: &str) -> Complex {
+        return Program::compile(source).unwrap().eval(Z, C);
+    }
+    fn close(a: Complex, b: Complex) -> bool {
+        return (a.re - b.re).abs() < 1e-12 && (a.im - b.im).abs() < 1e-12;
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        assert!(close(eval("2z"), eval("2*z")));
+        assert!(close(eval("z c"), cmul(Z, C)));
+        assert!(close(eval("3i z"), eval("3*i*z")));
+        assert!(close(eval("2(z + c)"), eval("2*(z + c)")));
+        assert!(close(eval("z sin(c)"), eval("z*sin(c)")));
+        // powers still bind first
+        assert!(close(eval("2z^2"), eval("2*(z^2)")));
+    }
+
+    #[test]
+    fn exponents_in_numbers() {
+        assert!(close(eval("2e5"), Complex { re: 2e5, im: 0. }));
+        assert!(close(eval("2e-3z"), eval("0.002*z")));
+        // no digits after the e, so it's euler's number
+        assert!(close(eval("2e"), Complex { re: 2. * std::f64::consts::E, im: 0. }));
+        assert!(close(eval("2e+z"), eval("2*e + z")));
+    }
+
+    #[test]
+    fn precedence() {
+        assert!(close(eval("-z^2"), eval("-(z^2)")));
+        assert!(close(eval("z^2^3"), eval("z^(2^3)")));
+        assert!(close(eval("z - c - 1"), eval("(z - c) - 1")));
+        assert!(close(eval("z^2 + c"), cadd(cmul(Z, Z), C)));
+    }
+
+    #[test]
+    fn error_positions() {
+        let at = |source: &str| Program::compile(source).unwrap_err().position;
+        assert_eq!(at("z^2 + $"), 6);
+        assert_eq!(at("z + (c"), 4);
+        assert_eq!(at("z + foo"), 4);
+        assert_eq!(at("sin z"), 4);
+        assert_eq!(at("z +"), 3);
+        assert_eq!(at("z)"), 1);
+        assert_eq!(at("1.2.3"), 0);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        for source in [
+            "(".repeat(100000) + "z" + &")".repeat(100000),
+            "-".repeat(100000) + "z",
+            "sin(".repeat(100000) + "z" + &")".repeat(100000),
+            "z^".repeat(100000) + "z",
+            "z+".repeat(100000) + "z",
+            "z ".repeat(100000),
+        ] {
+            let e = Program::compile(&source).unwrap_err();
+            assert_eq!(e.message, "formula is nested too deeply");
+        }
+        // but ordinary formulas are nowhere near it
+        assert!(Program::compile(&("(".repeat(20) + "z" + &")".repeat(20))).is_ok());
+        assert!(Program::compile(&("z+".repeat(100) + "c")).is_ok());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::fractal::{Plane, Progress, Sample};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::slice::{ParallelSlice, ParallelSliceMut};
+use std::sync::atomic::Ordering;
+
+// Mariani-Silver: only compute the border of a rectangle, and if every border pixel stayed
+// bounded fill the inside as bounded too, otherwise split in four and repeat
+// escaped pixels are never guessed, the smooth colourings differ even at equal iteration counts
+// exact for the connected interior of z^n + c, a (usually good) guess for anything else
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fill {
+    Exact,
+    Guess,
+    // compute the guessed pixels anyway and count how many guesses would have been wrong
+    Verify,
+}
+impl Fill {
+    pub const ALL: [Fill; 3] = [Fill::Exact, Fill::Guess, Fill::Verify];
+    pub fn output(&self) -> String {
+        match self {
+            Fill::Exact => return String::from("compute every pixel"),
+            Fill::Guess => return String::from("guess solid regions"),
+            Fill::Verify => return String::from("guess and verify"),
+        }
+    }
+}
+
+// rectangles start this big, and bands of this many lines are filled in parallel
+const TILE: usize = 64;
+// below this the border is most of the rectangle anyway
+const SMALLEST: usize = 4;
+
+// the full resolution pass, keeping known pixels and every done-th one (from the previous pass)
+// returns how many pixels were guessed and, when verifying, how many of those were wrong
+pub fn subdivide(
+    plane: &Plane,
+    samples: &mut [Sample],
+    known: &[bool],
+    done: usize,
+    fill: Fill,
+    progress: &Progress,
+) -> Option<(usize, usize)> {
+    let width = plane.width as usize;
+    let counts: Option<Vec<(usize, usize)>> = samples
+        .par_chunks_mut(width * TILE)
+        .zip(known.par_chunks(width * TILE))
+        .enumerate()
+        .map(|(band, (rows, known))| {
+            let top = band * TILE;
+            let height = rows.len() / width;
+            let exact = known
+                .iter()
+                .enumerate()
+                .map(|(i, known)| {
+                    let (x, y) = (i % width, top + i / width);
+                    *known || done != 0 && x.is_multiple_of(done) && y.is_multiple_of(done)
+                })
+                .collect();
+            let mut band = Band {
+                plane,
+                rows,
+                exact,
+                width,
+                top,
+                verify: fill == Fill::Verify,
+                progress,
+                guessed: 0,
+                wrong: 0,
+            };
+            for x in (0..width).step_by(TILE) {
+                band.rect(x, 0, (x + TILE).min(width), height)?;
+            }
+            progress.lines.fetch_add(height, Ordering::Relaxed);
+            Some((band.guessed, band.wrong))
+        })
+        .collect();
+    return Some(
+        counts?
+            .iter()
+            .fold((0, 0), |(guessed, wrong), (g, w)| (guessed + g, wrong + w)),
+    );
+}
+
+// one band of lines, owned by a single thread
+struct Band<'a, 'b> {
+    plane: &'a Plane<'b>,
+    rows: &'a mut [Sample],
+    // which samples are real rather than block filled by an earlier pass
+    exact: Vec<bool>,
+    width: usize,
+    top: usize,
+    verify: bool,
+    progress: &'a Progress,
+    guessed: usize,
+    wrong: usize,
+}
+impl Band<'_, '_> {
+    fn get(&mut self, x: usize, y: usize) -> Option<Sample> {
+        let i = y * self.width + x;
+        if !self.exact[i] {
+            if self.progress.cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.rows[i] = self.plane.sample(x, self.top + y);
+            self.exact[i] = true;
+        }
+        return Some(self.rows[i]);
+    }
+    fn interior(&mut self, x: usize, y: usize) -> Option<bool> {
+        return Some(self.get(x, y)?.iterations >= self.plane.maxitr as i32);
+    }
+    // x1 and y1 are exclusive
+    fn rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) -> Option<()> {
+        if x1 - x0 <= SMALLEST || y1 - y0 <= SMALLEST {
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    self.get(x, y)?;
+                }
+            }
+            return Some(());
+        }
+        let first = self.get(x0, y0)?;
+        let mut uniform = true;
+        for x in x0..x1 {
+            uniform &= self.interior(x, y0)?;
+            uniform &= self.interior(x, y1 - 1)?;
+        }
+        for y in y0..y1 {
+            uniform &= self.interior(x0, y)?;
+            uniform &= self.interior(x1 - 1, y)?;
+        }
+        if uniform {
+            for y in y0 + 1..y1 - 1 {
+                for x in x0 + 1..x1 - 1 {
+                    let i = y * self.width + x;
+                    if self.exact[i] {
+                        continue;
+                    }
+                    self.guessed += 1;
+                    if self.verify {
+                        if !self.interior(x, y)? {
+                            self.wrong += 1;
+                        }
+                    } else {
+                        self.rows[i] = first;
+                    }
+                }
+            }
+            return Some(());
+        }
+        let (xm, ym) = ((x0 + x1) / 2, (y0 + y1) / 2);
+        self.rect(x0, y0, xm, ym)?;
+        self.rect(xm, y0, x1, ym)?;
+        self.rect(x0, ym, xm, y1)?;
+        self.rect(xm, ym, x1, y1)?;
+        return Some(());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::expr::ExprFormula;
+use crate::fractal::{cadd, conj, integer, mandel2, norm, power, Complex};
+
+// z^-n + c is singular at z = 0, so negative powers start the orbit at c and
+// treat landing (almost) on zero as escaping, since the next step blows up
+const INVERSE_BAILOUT: f64 = 1e8;
+
+pub trait Formula: Send + Sync {
+    // one iteration of z -> f(z, c)
+    fn step(&self, z: Complex, c: Complex) -> Complex;
+    fn exponent(&self) -> Complex {
+        Complex { re: 2., im: 0. }
+    }
+    // where the orbit starts in the parameter plane
+    fn critical(&self, c: Complex) -> Complex {
+        if self.exponent().re < 0. {
+            return c;
+        }
+        Complex { re: 0., im: 0. }
+    }
+    // squared escape radius
+    fn bailout(&self) -> f64 {
+        if self.exponent().re < 0. {
+            return INVERSE_BAILOUT;
+        }
+        4.
+    }
+    // the radius to colour with once z has escaped (to infinity, or to zero for negative powers)
+    fn escaped(&self, z: Complex, bailout: f64) -> Option<f64> {
+        let r = norm(z);
+        if r >= bailout {
+            return Some(r);
+        }
+        if self.exponent().re < 0. && r <= 1. / bailout {
+            return Some(1. / r);
+        }
+        None
+    }
+    // cheap test for parameters known to be inside the set
+    fn interior(&self, _c: Complex) -> bool {
+        false
+    }
+    fn escape(&self, z: Complex, c: Complex, maxitr: f64) -> (i32, f64) {
+        iterate(self, z, c, maxitr)
+    }
+    // Some(n) when this is exactly z^n + c for an integer n >= 2, which the deep zoom renderers rely on
+    fn degree(&self) -> Option<i32> {
+        None
+    }
+}
+pub fn iterate<F: Formula + ?Sized>(formula: &F, mut z: Complex, c: Complex, maxitr: f64) -> (i32, f64) {
+    let bailout = formula.bailout();
+    let mut iterations: i32 = 0;
+    let mut zold: Complex = z;
+    while iterations < maxitr as i32 {
+        if let Some(r) = formula.escaped(z, bailout) {
+            return (iterations, r);
+        }
+        z = formula.step(z, c);
+        if z == zold {
+            return (maxitr as i32, 0.);
+        }
+        if iterations % 25 == 0 {
+            zold = z;
+        }
+        iterations += 1;
+    }
+    return (iterations, norm(z));
+}
+
+pub struct Multibrot(pub Complex);
+impl Formula for Multibrot {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        cadd(power(z, self.0), c)
+    }
+    fn exponent(&self) -> Complex {
+        self.0
+    }
+    fn interior(&self, c: Complex) -> bool {
+        if integer(self.0) != Some(2) {
+            return false;
+        }
+        let (x0, y0) = (c.re, c.im);
+        if x0 > -1.25 && y0.abs() < 0.85 {
+            let q = (x0 - 0.25).powi(2) + y0 * y0;
+            if q * (q + (x0 - 0.25)) <= 0.25 * y0 * y0 {
+                return true;
+            }
+            if (x0 + 1.).powi(2) + y0 * y0 <= 0.0625 {
+                return true;
+            }
+            if (x0 + 0.125).powi(2) + (y0.abs() - 0.7445).powi(2) <= 0.0089 {
+                return true;
+            }
+        }
+        false
+    }
+    fn escape(&self, z: Complex, c: Complex, maxitr: f64) -> (i32, f64) {
+        if integer(self.0) == Some(2) && z.re == 0. && z.im == 0. {
+            return mandel2(c.re, c.im, maxitr);
+        }
+        iterate(self, z, c, maxitr)
+    }
+    fn degree(&self) -> Option<i32> {
+        integer(self.0).filter(|n| *n >= 2)
+    }
+}
+// (|x| + i|y|)^n + c
+pub struct BurningShip(pub Complex);
+impl Formula for BurningShip {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        let z = Complex {
+            re: z.re.abs(),
+            im: z.im.abs(),
+        };
+        cadd(power(z, self.0), c)
+    }
+    fn exponent(&self) -> Complex {
+        self.0
+    }
+}
+// conj(z)^n + c
+pub struct Tricorn(pub Complex);
+impl Formula for Tricorn {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        cadd(power(conj(z), self.0), c)
+    }
+    fn exponent(&self) -> Complex {
+        self.0
+    }
+}
+// |re(z^n)| + i im(z^n) + c
+pub struct Celtic(pub Complex);
+impl Formula for Celtic {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        let p = power(z, self.0);
+        Complex {
+            re: p.re.abs() + c.re,
+            im: p.im + c.im,
+        }
+    }
+    fn exponent(&self) -> Complex {
+        self.0
+    }
+}
+// |re(z^n)| + i |im(z^n)| + c
+pub struct Buffalo(pub Complex);
+impl Formula for Buffalo {
+    fn step(&self, z: Complex, c: Complex) -> Complex {
+        let p = power(z, self.0);
+        Complex {
+            re: p.re.abs() + c.re,
+            im: p.im.abs() + c.im,
+        }
+    }
+    fn exponent(&self) -> Complex {
+        self.0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FormulaKind {
+    Multibrot,
+    BurningShip,
+    Tricorn,
+    Celtic,
+    Buffalo,
+    Custom,
+}
+impl FormulaKind {
+    pub const ALL: [FormulaKind; 6] = [
+        FormulaKind::Multibrot,
+        FormulaKind::BurningShip,
+        FormulaKind::Tricorn,
+        FormulaKind::Celtic,
+        FormulaKind::Buffalo,
+        FormulaKind::Custom,
+    ];
+    pub fn output(&self) -> String {
+        match self {
+            FormulaKind::Multibrot => return String::from("Multibrot"),
+            FormulaKind::BurningShip => return String::from("Burning Ship"),
+            FormulaKind::Tricorn => return String::from("Tricorn"),
+            FormulaKind::Celtic => return String::from("Celtic"),
+            FormulaKind::Buffalo => return String::from("Buffalo"),
+            FormulaKind::Custom => return String::from("Custom"),
+        }
+    }
+    // custom is the last expression that compiled, the exponent doesn't apply to it
+    pub fn build(&self, exponent: Complex, custom: &ExprFormula) -> Box<dyn Formula> {
+        match self {
+            FormulaKind::Multibrot => Box::new(Multibrot(exponent)),
+            FormulaKind::BurningShip => Box::new(BurningShip(exponent)),
+            FormulaKind::Tricorn => Box::new(Tricorn(exponent)),
+            FormulaKind::Celtic => Box::new(Celtic(exponent)),
+            FormulaKind::Buffalo => Box::new(Buffalo(exponent)),
+            FormulaKind::Custom => Box::new(custom.clone()),
+        }
+    }
+}
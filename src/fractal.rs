@@ -1,8 +1,18 @@
+use crate::formula::{Formula, Multibrot};
 use crate::hsl;
+use crate::dd::{ddcomp, DDComplex};
+use crate::perturbation::{perturb, Reference, Series};
+use crate::simd::{mandel2x, mandel2x32, Simd};
+use crate::supersample::{average, supersample, Supersampling};
+use crate::viewport::Viewport;
 use crate::Color32;
 use crate::ColoringMode;
-use rayon::iter::IntoParallelIterator;
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::IndexedParallelIterator;
 use rayon::iter::ParallelIterator;
+use rayon::slice::{ParallelSlice, ParallelSliceMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 #[derive(Copy, Clone, PartialEq)]
 pub struct Coord {
     pub x: f64,
@@ -10,29 +20,38 @@ pub struct Coord {
 }
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Complex {
-    re: f64,
-    im: f64,
+    pub re: f64,
+    pub im: f64,
 }
-fn cmul(z1: Complex, z2: Complex) -> Complex {
+pub fn cmul(z1: Complex, z2: Complex) -> Complex {
     Complex {
         re: z1.re * z2.re - z1.im * z2.im,
         im: z1.re * z2.im + z1.im * z2.re,
     }
 }
-fn cadd(z1: Complex, z2: Complex) -> Complex {
+pub fn conj(z: Complex) -> Complex {
+    Complex { re: z.re, im: -z.im }
+}
+pub fn cadd(z1: Complex, z2: Complex) -> Complex {
     Complex {
         re: z1.re + z2.re,
         im: z1.im + z2.im,
     }
 }
-fn crec(z: Complex) -> Complex {
+pub fn csub(z1: Complex, z2: Complex) -> Complex {
+    Complex {
+        re: z1.re - z2.re,
+        im: z1.im - z2.im,
+    }
+}
+pub fn crec(z: Complex) -> Complex {
     let den = z.re * z.re + z.im * z.im;
     return Complex {
         re: z.re / den,
         im: -z.im / den,
     };
 }
-fn cpow(z: Complex, n: i32) -> Complex {
+pub fn cpow(z: Complex, n: i32) -> Complex {
     if n < 0 {
         return cpow(crec(z), -n);
     }
@@ -48,37 +67,464 @@ fn cpow(z: Complex, n: i32) -> Complex {
         return cmul(z, cpow(cmul(z, z), (n - 1) / 2));
     }
 }
-pub fn mandelbrot(
+// polar form z^w = exp(w ln z), used whenever the exponent isn't an integer
+pub fn cpowc(z: Complex, w: Complex) -> Complex {
+    if z.re == 0. && z.im == 0. {
+        return z;
+    }
+    let lnr = norm(z).ln() / 2.;
+    let theta = z.im.atan2(z.re);
+    let a = (w.re * lnr - w.im * theta).exp();
+    let b = w.re * theta + w.im * lnr;
+    return Complex {
+        re: a * b.cos(),
+        im: a * b.sin(),
+    };
+}
+pub fn cdiv(z1: Complex, z2: Complex) -> Complex {
+    cmul(z1, crec(z2))
+}
+pub fn cexp(z: Complex) -> Complex {
+    let a = z.re.exp();
+    Complex {
+        re: a * z.im.cos(),
+        im: a * z.im.sin(),
+    }
+}
+pub fn clog(z: Complex) -> Complex {
+    Complex {
+        re: norm(z).ln() / 2.,
+        im: z.im.atan2(z.re),
+    }
+}
+pub fn csin(z: Complex) -> Complex {
+    Complex {
+        re: z.re.sin() * z.im.cosh(),
+        im: z.re.cos() * z.im.sinh(),
+    }
+}
+pub fn ccos(z: Complex) -> Complex {
+    Complex {
+        re: z.re.cos() * z.im.cosh(),
+        im: -z.re.sin() * z.im.sinh(),
+    }
+}
+pub fn csinh(z: Complex) -> Complex {
+    Complex {
+        re: z.re.sinh() * z.im.cos(),
+        im: z.re.cosh() * z.im.sin(),
+    }
+}
+pub fn ccosh(z: Complex) -> Complex {
+    Complex {
+        re: z.re.cosh() * z.im.cos(),
+        im: z.re.sinh() * z.im.sin(),
+    }
+}
+pub fn integer(n: Complex) -> Option<i32> {
+    if n.im == 0. && n.re.fract() == 0. && n.re.abs() < i32::MAX as f64 {
+        return Some(n.re as i32);
+    }
+    None
+}
+pub fn power(z: Complex, n: Complex) -> Complex {
+    match integer(n) {
+        Some(n) => cpow(z, n),
+        None => cpowc(z, n),
+    }
+}
+// pixel spacing relative to the size of the coordinates, below these neighbouring pixels stop
+// being distinct in f64, then in double-double
+// above SINGLE pixels are still tens of f32 ulps apart, so f32 is plenty
+const SINGLE: f64 = 1e-5;
+const DOUBLE_DOUBLE: f64 = 1e-15;
+const PERTURBATION: f64 = 1e-31;
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Precision {
+    F32,
+    F64,
+    DoubleDouble,
+    Perturbation,
+}
+impl Precision {
+    // the deeper tiers only know how to iterate z^n + c, f32 only z^2 + c in the parameter plane
+    // from the pixel spacing, so any crop of a view (an export band or tile) gets the same tier
+    pub fn choose(viewport: &Viewport, formula: &dyn Formula, julia: Option<Coord>) -> Precision {
+        let spacing = Precision::spacing(viewport);
+        if spacing >= SINGLE && formula.degree() == Some(2) && julia.is_none() {
+            return Precision::F32;
+        }
+        if spacing >= DOUBLE_DOUBLE || formula.degree().is_none() {
+            return Precision::F64;
+        }
+        if spacing >= PERTURBATION || julia.is_some() {
+            return Precision::DoubleDouble;
+        }
+        Precision::Perturbation
+    }
+    // measured against the largest coordinate around, taken as at least 2 (the size of the set)
+    fn spacing(viewport: &Viewport) -> f64 {
+        let center = viewport.center.to_coord();
+        return viewport.pixel() / center.x.abs().max(center.y.abs()).max(2.);
+    }
+    // whether the view is deeper than this tier can tell pixels apart, which happens when the
+    // formula or julia mode rules out the deeper ones
+    pub fn exhausted(&self, viewport: &Viewport) -> bool {
+        let limit = match self {
+            Precision::F32 => SINGLE,
+            Precision::F64 => DOUBLE_DOUBLE,
+            Precision::DoubleDouble => PERTURBATION,
+            Precision::Perturbation => 0.,
+        };
+        return Precision::spacing(viewport) < limit;
+    }
+    pub fn output(&self) -> String {
+        match self {
+            Precision::F32 => return String::from("f32"),
+            Precision::F64 => return String::from("f64"),
+            Precision::DoubleDouble => return String::from("double-double"),
+            Precision::Perturbation => return String::from("perturbation"),
+        }
+    }
+}
+#[derive(Clone)]
+enum Kernel {
+    F64,
+    // z^2 + c from z = 0 in f64, a row at a time through mandel2x
+    Vector(Simd),
+    // the same in f32 through mandel2x32
+    Single(Simd),
+    DoubleDouble(DDComplex, i32),
+    // shared by every crop of the plane
+    Perturbation(Arc<Reference>, Arc<Series>),
+}
+// what a render actually did, for the stats line
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Stats {
+    pub precision: Precision,
+    pub skipped: usize,
+    // pixels filled in by subdivision, and how many of those were wrong when verifying
+    pub guessed: usize,
+    pub wrong: usize,
+    // the view is past what the precision can resolve, neighbouring pixels blur together
+    pub exhausted: bool,
+}
+// raw escape-time result for one pixel, kept so recolouring doesn't iterate again
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub iterations: i32,
+    pub r: f64,
+}
+// everything needed to iterate any pixel of one frame
+pub struct Plane<'a> {
+    viewport: Viewport,
+    // where the kernel was set up (the reference orbit's point), and from there to the viewport's
+    // center, which only differ for crops
     center: Coord,
-    scale: f64,
+    origin: Coord,
+    pub maxitr: f64,
+    formula: &'a dyn Formula,
+    julia: Option<Coord>,
+    kernel: Kernel,
+    pub width: i32,
+    pub height: i32,
+    pub stats: Stats,
+}
+impl<'a> Plane<'a> {
+    // None if cancelled while still building the reference orbit
+    pub fn new(
+        viewport: &Viewport,
+        maxitr: f64,
+        formula: &'a dyn Formula,
+        julia: Option<Coord>,
+        simd: Simd,
+        progress: &Progress,
+    ) -> Option<Plane<'a>> {
+        let (center, scale) = (&viewport.center, viewport.scale);
+        let (width, height) = (viewport.width, viewport.height);
+        let precision = Precision::choose(viewport, formula, julia);
+        let kernel = match (precision, formula.degree()) {
+            (Precision::F32, _) => Kernel::Single(simd),
+            (Precision::DoubleDouble, Some(n)) => {
+                Kernel::DoubleDouble(DDComplex::from_coord(center), n)
+            }
+            (Precision::Perturbation, Some(n)) => {
+                let reference = Reference::new(center, scale, n, maxitr, progress)?;
+                // corners and edge midpoints of the view
+                let (w, h) = (width as f64, height as f64);
+                let probes: Vec<Complex> = [(0., 0.), (0.5, 0.), (1., 0.), (0., 0.5), (1., 0.5), (0., 1.), (0.5, 1.), (1., 1.)]
+                    .iter()
+                    .map(|(x, y)| {
+                        let offset = viewport.offset(x * w, y * h);
+                        Complex { re: offset.x, im: offset.y }
+                    })
+                    .collect();
+                let radius = probes.iter().map(|dc| norm(*dc).sqrt()).fold(0., f64::max);
+                let series = Series::new(&reference, &probes, radius);
+                Kernel::Perturbation(Arc::new(reference), Arc::new(series))
+            }
+            // degree 2 means Multibrot(2), whose escape is exactly mandel2 in the parameter plane
+            (Precision::F64, Some(2)) if julia.is_none() && simd != Simd::Scalar => Kernel::Vector(simd),
+            _ => Kernel::F64,
+        };
+        let skipped = match &kernel {
+            Kernel::Perturbation(_, series) => series.skip,
+            _ => 0,
+        };
+        Some(Plane {
+            viewport: viewport.clone(),
+            center: center.to_coord(),
+            origin: Coord { x: 0., y: 0. },
+            maxitr,
+            formula,
+            julia,
+            kernel,
+            width,
+            height,
+            stats: Stats {
+                precision,
+                skipped,
+                guessed: 0,
+                wrong: 0,
+                exhausted: precision.exhausted(viewport),
+            },
+        })
+    }
+    // the w x h pixels at (x, y) as a plane of their own, sharing this one's kernel, so an export
+    // computes the reference orbit once rather than for every band or tile, and they all match
+    pub fn crop(&self, x: i32, y: i32, width: i32, height: i32) -> Plane<'a> {
+        let viewport = self.viewport.crop(x, y, width, height);
+        let shift = viewport.center.difference(&self.viewport.center);
+        Plane {
+            origin: Coord {
+                x: self.origin.x + shift.x,
+                y: self.origin.y + shift.y,
+            },
+            viewport,
+            center: self.center,
+            maxitr: self.maxitr,
+            formula: self.formula,
+            julia: self.julia,
+            kernel: self.kernel.clone(),
+            width,
+            height,
+            stats: self.stats,
+        }
+    }
+    // pixel (x, y), sampled at its center
+    pub fn sample(&self, x: usize, y: usize) -> Sample {
+        return self.at(self.viewport.sample(x, y));
+    }
+    // the given pixels of one line, vectorised where the kernel allows
+    pub fn sample_row(&self, xs: &[usize], y: usize) -> Vec<Sample> {
+        let offsets: Vec<Coord> = xs.iter().map(|x| self.viewport.sample(*x, y)).collect();
+        return self.at_all(&offsets);
+    }
+    // arbitrary points in pixel coordinates, for supersampling
+    pub fn sample_points(&self, points: &[(f64, f64)]) -> Vec<Sample> {
+        let offsets: Vec<Coord> = points.iter().map(|(x, y)| self.viewport.offset(*x, *y)).collect();
+        return self.at_all(&offsets);
+    }
+    // the point that far from the center
+    fn at(&self, offset: Coord) -> Sample {
+        let maxitr = self.maxitr;
+        // the deep kernels work from offsets to the exact center, not absolute coordinates
+        let (dx, dy) = (self.origin.x + offset.x, self.origin.y + offset.y);
+        let (x0, y0) = (self.center.x + dx, self.center.y + dy);
+        let (iterations, r) = match (&self.kernel, self.julia) {
+            (Kernel::Single(_), _) => {
+                if self.formula.interior(Complex { re: x0, im: y0 }) {
+                    (maxitr as i32, 0.)
+                } else {
+                    mandel2f(x0 as f32, y0 as f32, maxitr)
+                }
+            }
+            (Kernel::Perturbation(reference, series), _) => {
+                perturb(reference, series, Complex { re: dx, im: dy }, maxitr)
+            }
+            (Kernel::DoubleDouble(center, n), Some(c)) => ddcomp(
+                center.offset(dx, dy),
+                DDComplex::from_f64(c.x, c.y),
+                maxitr,
+                *n,
+            ),
+            (Kernel::DoubleDouble(center, n), None) => {
+                ddcomp(DDComplex::from_f64(0., 0.), center.offset(dx, dy), maxitr, *n)
+            }
+            (Kernel::F64 | Kernel::Vector(_), Some(c)) => juliacomp(self.formula, x0, y0, c, maxitr),
+            (Kernel::F64 | Kernel::Vector(_), None) => mandelcomp(self.formula, x0, y0, maxitr),
+        };
+        Sample { iterations, r }
+    }
+    // several points at once, through the vector kernel where there is one
+    fn at_all(&self, offsets: &[Coord]) -> Vec<Sample> {
+        let (simd, single) = match self.kernel {
+            Kernel::Vector(simd) => (simd, false),
+            Kernel::Single(simd) => (simd, true),
+            _ => return offsets.iter().map(|offset| self.at(*offset)).collect(),
+        };
+        let mut samples = vec![Sample { iterations: self.maxitr as i32, r: 0. }; offsets.len()];
+        // the interior checks stay scalar, only the rest goes through the vector kernel
+        let outside: Vec<(usize, Complex)> = offsets
+            .iter()
+            .map(|offset| Complex {
+                re: self.center.x + self.origin.x + offset.x,
+                im: self.center.y + self.origin.y + offset.y,
+            })
+            .enumerate()
+            .filter(|(_, c)| !self.formula.interior(*c))
+            .collect();
+        let x0: Vec<f64> = outside.iter().map(|(_, c)| c.re).collect();
+        let y0: Vec<f64> = outside.iter().map(|(_, c)| c.im).collect();
+        let escapes = if single {
+            let x0: Vec<f32> = x0.iter().map(|x0| *x0 as f32).collect();
+            let y0: Vec<f32> = y0.iter().map(|y0| *y0 as f32).collect();
+            mandel2x32(simd, &x0, &y0, self.maxitr)
+        } else {
+            mandel2x(simd, &x0, &y0, self.maxitr)
+        };
+        for ((i, _), (iterations, r)) in outside.iter().zip(escapes) {
+            samples[*i] = Sample { iterations, r };
+        }
+        return samples;
+    }
+}
+pub fn mandelbrot(
+    viewport: &Viewport,
+    maxitr: f64,
+    formula: &dyn Formula,
+    supersampling: Supersampling,
+    mode: ColoringMode,
+) -> Vec<u8> {
+    let progress = Progress::default();
+    let plane = Plane::new(viewport, maxitr, formula, None, Simd::best(), &progress).unwrap();
+    render(&plane, supersampling, mode, &progress).unwrap()
+}
+// same view mapping as mandelbrot, but every pixel is a starting z for the fixed c
+pub fn julia(
+    viewport: &Viewport,
     maxitr: f64,
-    exponent: i32,
-    width: i32,
-    height: i32,
+    formula: &dyn Formula,
+    c: Coord,
+    supersampling: Supersampling,
     mode: ColoringMode,
 ) -> Vec<u8> {
-    let buf: Vec<u8> = (0..height)
-        .into_par_iter()
-        .map(|y| {
-            renderline(
-                y as u32, scale, maxitr, center, exponent, width, height, mode,
-            )
+    let progress = Progress::default();
+    let plane = Plane::new(viewport, maxitr, formula, Some(c), Simd::best(), &progress).unwrap();
+    render(&plane, supersampling, mode, &progress).unwrap()
+}
+// the whole plane in one go, straight to colours
+pub fn render(plane: &Plane, supersampling: Supersampling, mode: ColoringMode, progress: &Progress) -> Option<Vec<u8>> {
+    let samples = iterate(plane, progress)?;
+    let mut subsamples = Vec::new();
+    if supersampling.enabled() {
+        subsamples = vec![Vec::new(); samples.len()];
+        supersample(plane, &samples, &mut subsamples, supersampling, progress)?;
+    }
+    return Some(colorize(&samples, &subsamples, plane.maxitr, mode));
+}
+// shared with whoever is waiting on the frame, so it can watch and abandon it
+#[derive(Default)]
+pub struct Progress {
+    pub cancelled: AtomicBool,
+    pub lines: AtomicUsize,
+}
+// every frame is computed coarse to fine, one sample per step x step block
+pub const PASSES: [usize; 4] = [8, 4, 2, 1];
+// None if the frame was cancelled part way through
+pub fn iterate(plane: &Plane, progress: &Progress) -> Option<Vec<Sample>> {
+    let size = (plane.width * plane.height) as usize;
+    let mut samples = vec![Sample { iterations: 0, r: 0. }; size];
+    refine(plane, &mut samples, &vec![false; size], 1, 0, progress)?;
+    return Some(samples);
+}
+// one pass: sample every step-th pixel and fill its block with it, skipping the pixels
+// the previous pass (with step done, 0 for none) already computed, and leaving the
+// known ones (carried over from an earlier frame) alone
+pub fn refine(
+    plane: &Plane,
+    samples: &mut [Sample],
+    known: &[bool],
+    step: usize,
+    done: usize,
+    progress: &Progress,
+) -> Option<()> {
+    let width = plane.width as usize;
+    samples
+        .par_chunks_mut(width * step)
+        .zip(known.par_chunks(width * step))
+        .enumerate()
+        .map(|(band, (rows, known))| {
+            let y = band * step;
+            let xs: Vec<usize> = (0..width)
+                .step_by(step)
+                .filter(|x| !(known[*x] || done != 0 && x.is_multiple_of(done) && y.is_multiple_of(done)))
+                .collect();
+            // in chunks so cancelling still gets noticed, a single line can take seconds at deep zoom
+            for xs in xs.chunks(16) {
+                if progress.cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+                for (x, sample) in xs.iter().zip(plane.sample_row(xs, y)) {
+                    for (row, known) in rows.chunks_mut(width).zip(known.chunks(width)) {
+                        for i in *x..(x + step).min(width) {
+                            if !known[i] {
+                                row[i] = sample;
+                            }
+                        }
+                    }
+                }
+            }
+            progress.lines.fetch_add(1, Ordering::Relaxed);
+            Some(())
         })
-        .flatten()
-        .collect();
-    buf
+        .collect()
+}
+// the samples (or subsamples) of a finished frame moved by whole pixels, and which of them are still valid
+pub fn shift<T: Clone>(samples: &[T], empty: T, width: i32, height: i32, dx: i32, dy: i32) -> (Vec<T>, Vec<bool>) {
+    let size = (width * height) as usize;
+    let mut shifted = vec![empty; size];
+    let mut known = vec![false; size];
+    for y in 0..height {
+        for x in 0..width {
+            let (ox, oy) = (x + dx, y + dy);
+            if ox >= 0 && ox < width && oy >= 0 && oy < height {
+                shifted[(y * width + x) as usize] = samples[(oy * width + ox) as usize].clone();
+                known[(y * width + x) as usize] = true;
+            }
+        }
+    }
+    return (shifted, known);
+}
+// pixels with subsamples (subsamples may be shorter than samples, or empty) get their average
+pub fn colorize(samples: &[Sample], subsamples: &[Vec<Sample>], maxitr: f64, mode: ColoringMode) -> Vec<u8> {
+    samples
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, sample)| match subsamples.get(i) {
+            Some(subsamples) if !subsamples.is_empty() => average(subsamples, maxitr, mode),
+            _ => color(*sample, maxitr, mode),
+        })
+        .collect()
+}
+pub fn color(sample: Sample, maxitr: f64, mode: ColoringMode) -> [u8; 4] {
+    let Sample { iterations, r } = sample;
+    match mode {
+        ColoringMode::Hsl(shift, normal, range) => hslcolor(iterations, maxitr, r, shift, normal, range),
+        ColoringMode::Monochrome(color, range) => monocolor(iterations, maxitr, r, color, range),
+        ColoringMode::Funky(shift) => funkycolor(iterations, r, shift),
+    }
 }
 fn hslcolor(iterations: i32, maxitr: f64, r: f64, shift: f64, normal: f64, range: f64) -> [u8; 4] {
     if iterations >= maxitr as i32 {
         return [0, 0, 0, 255];
     }
     let iterations: f64 = iterations as f64 + 1.0 - (((r).ln() / 2.0).ln()) / 2.0_f64.ln();
-    let rgba = hsl::hsl_to_rgba(
-        ((((iterations / maxitr)  *(range/360.) + (shift / 360.))) % 1.) * (normal),
+    hsl::hsl_to_rgba(
+        (((iterations / maxitr) * (range / 360.) + (shift / 360.)) % 1.) * (normal),
         1.,
         0.5,
-    );
-    rgba
+    )
 }
 fn monocolor(iterations: i32, maxitr: f64, r: f64, color: Color32, range: f64) -> [u8; 4] {
     if iterations >= maxitr as i32 {
@@ -87,8 +533,8 @@ fn monocolor(iterations: i32, maxitr: f64, r: f64, color: Color32, range: f64) -
     let iterations: f64 = iterations as f64 + 1.0 - (((r).ln() / 2.0).ln()) / 2.0_f64.ln();
 
     let mut color = color.to_array();
-    for i in 0..3 {
-        color[i] = ((iterations / maxitr) * color[i] as f64 * range) as u8;
+    for channel in color.iter_mut().take(3) {
+        *channel = ((iterations / maxitr) * *channel as f64 * range) as u8;
     }
     color[3] = 255;
     color
@@ -98,17 +544,11 @@ fn funkycolor(iterations: i32, r: f64, shift: f64) -> [u8; 4] {
     if r <= 4. {
         return [0, 0, 0, 255];
     }
-    let rgba = hsl::hsl_to_rgba(shift / 360. + (iterations as f64 / 800. * r), 1., 0.5);
-    rgba
+    hsl::hsl_to_rgba(shift / 360. + (iterations as f64 / 800. * r), 1., 0.5)
 }
 
-pub fn px(x: f64, scale: f64, ox: f64, width: i32) -> f64 {
-    return (ox) + ((2.0 * ((x) / (width as f64 - 1.)) - 1.0) * 1.235 * scale);
-}
-pub fn py(y: f64, scale: f64, oy: f64, height: i32) -> f64 {
-    return oy + (2.0 * ((y) / (height as f64 - 1.)) - 1.0) * 1.12 * scale;
-}
-fn mandel2(x0: f64, y0: f64, maxitr: f64) -> (i32, f64) {
+// hand-unrolled z^2 + c, the parameter plane interior checks live in Multibrot::interior
+pub fn mandel2(x0: f64, y0: f64, maxitr: f64) -> (i32, f64) {
     let mut iterations: i32 = 0;
     let mut x2: f64 = 0.0;
     let mut y2: f64 = 0.0;
@@ -116,20 +556,6 @@ fn mandel2(x0: f64, y0: f64, maxitr: f64) -> (i32, f64) {
     let mut y: f64 = 0.0;
     let mut xold: f64 = 0.0;
     let mut yold: f64 = 0.0;
-    
-    if x0 > -1.25 && y0.abs() < 0.85{
-
-    let q = (x0 - 0.25).powi(2) + y0 * y0;
-    if q * (q + (x0 - 0.25)) <= 0.25 * y0 * y0 {
-        return (maxitr as i32, 0.0);
-    }
-    if (x0 + 1.).powi(2) + y0 * y0 <= 0.0625 {
-        return (maxitr as i32, 0.0);
-    }
-    if (x0 + 0.125).powi(2) + (y0.abs() - 0.7445).powi(2) <= 0.0089{
-        return (maxitr as i32, 0.0);
-    }
-}
 
     while x2 + y2 < 4.0 && iterations < maxitr as i32 {
         y = y * (x + x) + y0;
@@ -143,66 +569,71 @@ fn mandel2(x0: f64, y0: f64, maxitr: f64) -> (i32, f64) {
             xold = x;
             yold = y;
         }
-        iterations = iterations + 1;
+        iterations += 1;
     }
     return (iterations, x2 + y2);
     
 }
-fn renderline(
-    linenumber: u32,
-    scale: f64,
-    maxitr: f64,
-    center: Coord,
-    exponent: i32,
-    width: i32,
-    height: i32,
-    mode: ColoringMode,
-) -> Vec<u8> {
-    let mut x0: f64;
-    let mut line: Vec<u8> = Vec::new();
-    let y0 = py(linenumber as f64, scale, center.y, height);
-    for x in 0..width {
-        x0 = px(x as f64, scale, center.x, width);
-        let (iterations, r) = mandelcomp(x0, y0, maxitr, exponent);
-        match mode {
-            ColoringMode::Hsl(shift, normal, range) => {
-                line.extend_from_slice(&hslcolor(iterations, maxitr, r, shift, normal, range))
-            }
-            ColoringMode::Monochrome(color, range) => {
-                line.extend_from_slice(&monocolor(iterations, maxitr, r, color, range))
-            }
-            ColoringMode::Funky(shift) => line.extend_from_slice(&funkycolor(iterations, r, shift)),
-        }
-    }
-    return line;
-}
-pub fn mandelcomp(x0: f64, y0: f64, maxitr: f64, n: i32) -> (i32, f64) {
-    if n == 2 {
-        return mandel2(x0, y0, maxitr);
-    }
-    let mut zold: Complex = Complex { re: 0., im: 0. };
+// mandel2 in f32 for shallow zooms, r is widened back to f64
+pub fn mandel2f(x0: f32, y0: f32, maxitr: f64) -> (i32, f64) {
     let mut iterations: i32 = 0;
-    let c = Complex { re: x0, im: y0 };
-    let mut z: Complex = Complex { re: 0., im: 0. };
-    while z.re * z.re + z.im * z.im < 4. && iterations < maxitr as i32 {
-        z = cadd(cpow(z, n), c);
-        if z == zold {
-            return (maxitr as i32, 0.);
+    let mut x2: f32 = 0.0;
+    let mut y2: f32 = 0.0;
+    let mut x: f32 = 0.0;
+    let mut y: f32 = 0.0;
+    let mut xold: f32 = 0.0;
+    let mut yold: f32 = 0.0;
+
+    while x2 + y2 < 4.0 && iterations < maxitr as i32 {
+        y = y * (x + x) + y0;
+        x = x2 - y2 + x0;
+        x2 = x * x;
+        y2 = y * y;
+        if x == xold && y == yold {
+            return (maxitr as i32, (x2 + y2) as f64);
         }
         if iterations % 25 == 0 {
-            zold = z;
+            xold = x;
+            yold = y;
         }
         iterations += 1;
     }
-    return (iterations, z.re * z.re + z.im * z.im);
+    return (iterations, (x2 + y2) as f64);
+}
+pub fn norm(z: Complex) -> f64 {
+    z.re * z.re + z.im * z.im
+}
+pub fn mandelcomp(formula: &dyn Formula, x0: f64, y0: f64, maxitr: f64) -> (i32, f64) {
+    let c = Complex { re: x0, im: y0 };
+    if formula.interior(c) {
+        return (maxitr as i32, 0.);
+    }
+    return formula.escape(formula.critical(c), c, maxitr);
+}
+pub fn juliacomp(formula: &dyn Formula, x0: f64, y0: f64, c: Coord, maxitr: f64) -> (i32, f64) {
+    let c = Complex { re: c.x, im: c.y };
+    return formula.escape(Complex { re: x0, im: y0 }, c, maxitr);
 }
-pub fn mandelcomplist(x0: f64, y0: f64, maxitr: f64, n: i32) -> (i32, Vec<Coord>, i32) {
+pub fn mandelcomplist(formula: &dyn Formula, x0: f64, y0: f64, maxitr: f64) -> (i32, Vec<Coord>, i32) {
+    let c = Complex { re: x0, im: y0 };
+    return orbit(formula, formula.critical(c), c, maxitr);
+}
+pub fn juliacomplist(
+    formula: &dyn Formula,
+    x0: f64,
+    y0: f64,
+    c: Coord,
+    maxitr: f64,
+) -> (i32, Vec<Coord>, i32) {
+    let c = Complex { re: c.x, im: c.y };
+    return orbit(formula, Complex { re: x0, im: y0 }, c, maxitr);
+}
+fn orbit(formula: &dyn Formula, mut z: Complex, c: Complex, maxitr: f64) -> (i32, Vec<Coord>, i32) {
     let mut points: Vec<Coord> = Vec::new();
     let mut iterations: i32 = 0;
-    let c = Complex { re: x0, im: y0 };
-    let mut z: Complex = Complex { re: 0., im: 0. };
-    while z.re * z.re + z.im * z.im < 1000. && iterations < maxitr as i32 {
-        z = cadd(cpow(z, n), c);
+    let bailout = formula.bailout().max(1000.);
+    while formula.escaped(z, bailout).is_none() && iterations < maxitr as i32 {
+        z = formula.step(z, c);
         iterations += 1;
         if points.contains(&Coord { x: z.re, y: z.im }) {
             return (maxitr as i32, points, iterations);
@@ -214,12 +645,11 @@ pub fn mandelcomplist(x0: f64, y0: f64, maxitr: f64, n: i32) -> (i32, Vec<Coord>
 }
 pub fn piapprox() -> f64 {
     let epsilon = 0.0000001;
-    let (iter, _) = mandelcomp(-0.75, epsilon, 1000000000000000000000000000000000., 2);
+    let (iter, _) = mandelcomp(
+        &Multibrot(Complex { re: 2., im: 0. }),
+        -0.75,
+        epsilon,
+        1000000000000000000000000000000000.,
+    );
     return iter as f64 * epsilon;
 }
-pub fn xp(x: f64, ox: f64, zoom: f64, width: i32) -> i32 {
-    return ((((((x - ox) / zoom) / 1.235) + 1.) / 2.) * width as f64) as i32;
-}
-pub fn yp(y: f64, oy: f64, zoom: f64, height: i32) -> i32 {
-    return ((((((y - oy) / zoom) / 1.12) + 1.) / 2.) * height as f64) as i32;
-}
\ No newline at end of file
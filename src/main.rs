@@ -1,40 +1,73 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
+#![allow(clippy::needless_return, clippy::too_many_arguments)]
 use eframe::egui;
 use eframe::IconData;
 use egui::*;
 use egui_extras::RetainedImage;
-use std::time::Instant;
-pub const WIDTH: i32 = 1976 / 2;
-pub const HEIGHT: i32 = 1792 / 2;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+const PREVIEW: i32 = 192;
+const THUMBNAIL: i32 = 64;
+// how far in "back to mandelbrot" is after opening a julia set from a file
+const PARAMETER_SCALE: f64 = 0.1;
+// room left under the view for the pointer readout
+const READOUT: f32 = 80.;
+// degrees per scroll notch with alt held
+const ROTATE_STEP: f64 = 15.;
 
+mod bookmarks;
+mod dd;
+mod export;
+mod expr;
+mod fill;
+mod formula;
 mod fractal;
 mod hsl;
-use crate::fractal::{mandelbrot, mandelcomplist, px, py, Coord};
+mod perturbation;
+mod precision;
+mod session;
+mod simd;
+mod supersample;
+mod viewport;
+mod worker;
+use crate::bookmarks::Bookmark;
+use crate::export::{read_text, Export};
+use crate::expr::{ExprFormula, ParseError, Program};
+use crate::fill::Fill;
+use crate::formula::{FormulaKind, Multibrot};
+use crate::precision::{BigCoord, Decimal};
+use crate::simd::Simd;
+use crate::supersample::{Pattern, Supersampling};
+use crate::fractal::{
+    colorize, julia, juliacomplist, mandelbrot, mandelcomplist, shift, Complex, Coord, Precision,
+    Sample, Stats,
+};
+use crate::viewport::Viewport;
+use crate::worker::{Job, Reuse};
 //TODO
-// allow negative powers (look on wikipedia, theres a cool formula thing that you dont understand)
 // better colours
 // styling
 fn main() -> Result<(), eframe::Error> {
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.icon_data = Some(IconData {
-        rgba: mandelbrot(
-            Coord { x: -0.765, y: 0. },
-            1.,
-            250.,
-            2,
-            256,
-            256,
-            ColoringMode::Hsl(0., 1., 360.),
-        ),
-        width: 256,
-        height: 256,
-    });
-    native_options.maximized = true;
+    let native_options = eframe::NativeOptions {
+        icon_data: Some(IconData {
+            rgba: mandelbrot(
+                &Viewport::new(BigCoord::new(-0.765, 0.), 1.1, 256, 256),
+                250.,
+                &Multibrot(Complex { re: 2., im: 0. }),
+                Supersampling::OFF,
+                ColoringMode::Hsl(0., 1., 360.),
+            ),
+            width: 256,
+            height: 256,
+        }),
+        maximized: true,
+        ..Default::default()
+    };
 
     eframe::run_native(
         "Mandelbrot Explorer",
         native_options,
-        Box::new(|_cc| Box::<Content>::default()),
+        Box::new(|_cc| Box::new(Content::startup())),
     )
 }
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -52,54 +85,208 @@ impl ColoringMode {
         }
     }
 }
+// the mode and its parameters, as written into exported images
+impl std::fmt::Display for ColoringMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ColoringMode::Hsl(shift, normal, range) => write!(f, "hsl {} {} {}", shift, normal, range),
+            ColoringMode::Monochrome(color, range) => {
+                write!(f, "monochrome #{:02x}{:02x}{:02x} {}", color.r(), color.g(), color.b(), range)
+            }
+            ColoringMode::Funky(shift) => write!(f, "funky {}", shift),
+        }
+    }
+}
+impl std::str::FromStr for ColoringMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<ColoringMode, String> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |i: usize| -> Result<f64, String> {
+            let word = words.get(i).ok_or(format!("`{}` is missing a number", s))?;
+            word.parse().map_err(|_| format!("`{}` is not a number", word))
+        };
+        match words.first() {
+            Some(&"hsl") => return Ok(ColoringMode::Hsl(number(1)?, number(2)?, number(3)?)),
+            Some(&"monochrome") => {
+                let hex = words.get(1).and_then(|word| word.strip_prefix('#')).unwrap_or("");
+                let color = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 6)
+                    .ok_or(format!("`{}` has no #rrggbb colour", s))?;
+                let [_, r, g, b] = color.to_be_bytes();
+                return Ok(ColoringMode::Monochrome(Color32::from_rgb(r, g, b), number(2)?));
+            }
+            Some(&"funky") => return Ok(ColoringMode::Funky(number(1)?)),
+            _ => return Err(format!("unknown colouring `{}`", s)),
+        }
+    }
+}
+// the value for key in an image's text chunks, noting it down if it isn't there
+fn field<'a>(text: &'a [(String, String)], key: &str, problems: &mut Vec<String>) -> Option<&'a str> {
+    let value = text.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str());
+    if value.is_none() {
+        problems.push(format!("{} is missing", key));
+    }
+    return value;
+}
+// what the last save, open or import did and anything it couldn't, or why it failed
+type Status = Option<Result<(String, Vec<String>), String>>;
+fn status(ui: &mut Ui, status: &Status) {
+    match status {
+        Some(Ok((done, problems))) => {
+            ui.label(done);
+            for problem in problems {
+                ui.colored_label(Color32::LIGHT_RED, problem);
+            }
+        }
+        Some(Err(e)) => {
+            ui.colored_label(Color32::LIGHT_RED, e);
+        }
+        None => {}
+    }
+}
+// a + bi as written by the export, a and b in any form f64 parses
+fn parse_complex(s: &str) -> Option<Complex> {
+    let s = s.trim().strip_suffix('i')?;
+    // the sign between the parts, not one at the start or in an exponent
+    let split = s
+        .char_indices()
+        .skip(1)
+        .filter(|(i, c)| (*c == '+' || *c == '-') && !s[..*i].ends_with(['e', 'E']))
+        .last()?
+        .0;
+    Some(Complex {
+        re: s[..split].parse().ok()?,
+        im: s[split..].parse().ok()?,
+    })
+}
+// everything that changes the iteration counts, as opposed to just the colours
+type View = (Viewport, i32, Complex, Option<Coord>, FormulaKind, ExprFormula, Fill, Simd, Supersampling);
 struct Content {
-    center: Coord,
-    zoom: f64,
+    // sized in physical pixels, following the panel
+    viewport: Viewport,
+    pixels_per_point: f32,
+    center_text: [String; 2],
+    center_error: Option<String>,
     image: RetainedImage,
-    time: f64,
+    // the last finished frame, shown (moved and scaled to fit) until the next one arrives
+    frame: worker::Frame,
+    job: Option<Job>,
+    // the last complete frame's view and samples, kept apart from the frame above (which later
+    // jobs' coarse passes replace) so panning can keep reusing them until the next one completes
+    finished: Option<(View, Vec<Sample>, Vec<Vec<Sample>>)>,
+    // sub-pixel part of the drag, carried until it adds up to a whole pixel
+    drag: Vec2,
     maxitr: i32,
-    exponent: i32,
-    prev: (Coord, f64, i32, i32, ColoringMode, bool),
+    exponent: Complex,
+    formula: FormulaKind,
+    fill: Fill,
+    simd: Simd,
+    supersampling: Supersampling,
+    custom: ExprFormula,
+    custom_source: [String; 2],
+    custom_error: [Option<ParseError>; 2],
+    animate: bool,
+    animation: (i32, i32, f64),
+    phase: f64,
+    prev: Option<View>,
+    prev_coloring: ColoringMode,
     coloring: ColoringMode,
     pi: f64,
     axes: bool,
     orbits: bool,
+    julia: Option<Coord>,
+    parameter_view: (BigCoord, f64),
+    preview: bool,
+    preview_image: Option<(Coord, RetainedImage)>,
+    export_size: [i32; 2],
+    export_path: String,
+    // in resumable tiles, for posters too big to render in one go
+    export_tiled: bool,
+    export: Option<Export>,
+    // the path written or why it failed, from the last export
+    export_result: Option<Result<String, String>>,
+    open_path: String,
+    // what was restored and whatever couldn't be, or why nothing could
+    restored: Status,
+    session_path: String,
+    // what the last save or open did, in the same form
+    session: Status,
+    show_bookmarks: bool,
+    bookmarks: Vec<Bookmark>,
+    bookmark_name: String,
+    // for importing and exporting the list
+    bookmarks_path: String,
+    // what the last jump, import or export did
+    bookmarks_status: Status,
 }
 impl Default for Content {
     fn default() -> Self {
         Self {
-            center: Coord { x: -0.765, y: 0. },
-            zoom: 1.,
-            image: RetainedImage::from_color_image(
-                "mandel",
-                ColorImage::from_rgba_unmultiplied(
-                    [WIDTH as usize, HEIGHT as usize],
-                    &mandelbrot(
-                        Coord { x: -0.765, y: 0. },
-                        1.,
-                        300.,
-                        2,
-                        WIDTH,
-                        HEIGHT,
-                        ColoringMode::Hsl(0., 1., 360.),
-                    ),
-                ),
-            ),
-            time: 50000000.,
+            viewport: Viewport::new(BigCoord::new(-0.765, 0.), 1., 988, 896),
+            pixels_per_point: 1.,
+            center_text: [String::new(), String::new()],
+            center_error: None,
+            image: RetainedImage::from_color_image("mandel", ColorImage::new([1, 1], Color32::BLACK)),
+            frame: worker::Frame {
+                viewport: Viewport::new(BigCoord::new(-0.765, 0.), 1., 988, 896),
+                maxitr: 300.,
+                samples: Vec::new(),
+                subsamples: Vec::new(),
+                stats: Stats {
+                    precision: Precision::F64,
+                    skipped: 0,
+                    guessed: 0,
+                    wrong: 0,
+                    exhausted: false,
+                },
+                time: 50000000.,
+                step: 1,
+                complete: true,
+            },
+            job: None,
+            finished: None,
+            drag: Vec2::ZERO,
             maxitr: 300,
-            exponent: 2,
-            prev: (
-                Coord { x: -0.765, y: 0. },
-                1.,
-                0,
-                2,
-                ColoringMode::Hsl(0., 1., 360.),
-                false,
-            ),
+            exponent: Complex { re: 2., im: 0. },
+            formula: FormulaKind::Multibrot,
+            fill: Fill::Exact,
+            simd: Simd::best(),
+            supersampling: Supersampling::OFF,
+            custom: ExprFormula {
+                step: Program::compile("z^2 + c").unwrap(),
+                start: Program::compile("0").unwrap(),
+                bailout: 2.,
+            },
+            custom_source: [String::from("z^2 + c"), String::from("0")],
+            custom_error: [None, None],
+            animate: false,
+            animation: (2, 3, 0.25),
+            phase: 0.,
+            prev: None,
+            prev_coloring: ColoringMode::Hsl(0., 1., 360.),
             coloring: ColoringMode::Hsl(0., 1., 360.),
             pi: 0.,
             axes: false,
             orbits: false,
+            julia: None,
+            parameter_view: (BigCoord::new(-0.765, 0.), 1.),
+            preview: false,
+            preview_image: None,
+            export_size: [7680, 4320],
+            export_path: String::from("multibrot.png"),
+            export_tiled: false,
+            export: None,
+            export_result: None,
+            open_path: String::new(),
+            restored: None,
+            session_path: String::from("session.toml"),
+            session: None,
+            show_bookmarks: false,
+            bookmarks: Vec::new(),
+            bookmark_name: String::new(),
+            bookmarks_path: String::from("bookmarks.csv"),
+            bookmarks_status: None,
         }
     }
 }
@@ -109,32 +296,102 @@ impl eframe::App for Content {
         let frame = Frame::side_top_panel(&ctx.style()).inner_margin(0.0);
         egui::SidePanel::left("my_left_panel")
             .frame(frame)
-            .exact_width(WIDTH as f32 + 1.0)
-            .resizable(false)
+            .default_width(988.)
+            .resizable(true)
             .show(ctx, |ui| {
                 ui.vertical(|ui| {
-                    ui.put(Rect{min:Pos2{x:0.,y:0.}, max:Pos2{x:WIDTH as f32, y:HEIGHT as f32}}, egui::Image::new(self.image.texture_id(ctx), Vec2{x: WIDTH as f32, y: HEIGHT as f32}));
-                    let painter = egui::Painter::new(ctx.clone(), egui::LayerId::new(egui::Order::Foreground, egui::Id::new("mandel")),Rect{min:Pos2{x:0.,y:0.}, max:Pos2{x:WIDTH as f32, y:HEIGHT as f32}});
+                    // the view fills the panel, rendered at the screen's real resolution
+                    self.pixels_per_point = ctx.pixels_per_point();
+                    let available = ui.available_size() - Vec2::new(0., READOUT);
+                    self.viewport.width = ((available.x * self.pixels_per_point) as i32).max(2);
+                    self.viewport.height = ((available.y * self.pixels_per_point) as i32).max(2);
+                    let center = self.viewport.center.to_coord();
+                    let view = self.rect();
+                    let scale = self.pixels_per_point;
+                    // a point of the plane to where it is in the panel
+                    let viewport = &self.viewport;
+                    let to_screen = |p: Coord| {
+                        let (x, y) = viewport.screen(Coord { x: p.x - center.x, y: p.y - center.y });
+                        Pos2 { x: x as f32 / scale, y: y as f32 / scale }
+                    };
+                    ui.allocate_rect(view, Sense::hover());
+                    let background = ui.painter_at(view);
+                    background.rect_filled(view, 0., Color32::BLACK);
+                    // a quad rather than a rect, the frame may be turned relative to the view
+                    let mut mesh = Mesh::with_texture(self.image.texture_id(ctx));
+                    let uvs = [pos2(0., 0.), pos2(1., 0.), pos2(1., 1.), pos2(0., 1.)];
+                    for (corner, uv) in self.placement().into_iter().zip(uvs) {
+                        mesh.vertices.push(epaint::Vertex { pos: corner, uv, color: Color32::WHITE });
+                    }
+                    mesh.add_triangle(0, 1, 2);
+                    mesh.add_triangle(0, 2, 3);
+                    background.add(mesh);
+                    let painter = egui::Painter::new(ctx.clone(), egui::LayerId::new(egui::Order::Foreground, egui::Id::new("mandel")),view);
                     if self.axes{
-                    painter.vline(fractal::xp(0.0,self.center.x,self.zoom,WIDTH) as f32,0.0..=HEIGHT as f32,  egui::Stroke{width: 5., color: Color32::WHITE});
-                    painter.hline(0.0..=WIDTH as f32,fractal::yp(0.0,self.center.y,self.zoom,HEIGHT) as f32,  egui::Stroke{width: 5., color: Color32::WHITE});
+                    // long enough to cross the view at any angle
+                    let reach = viewport.pixel() * (viewport.width + viewport.height) as f64;
+                    let stroke = egui::Stroke{width: 5., color: Color32::WHITE};
+                    painter.line_segment([to_screen(Coord{x: center.x - reach, y: 0.}), to_screen(Coord{x: center.x + reach, y: 0.})], stroke);
+                    painter.line_segment([to_screen(Coord{x: 0., y: center.y - reach}), to_screen(Coord{x: 0., y: center.y + reach})], stroke);
                 }
 
-                    let pos = ctx.pointer_hover_pos();
-                    if pos.is_some() {
-                        let pos = pos.unwrap();
-                        if pos.x < WIDTH as f32 && pos.y < HEIGHT as f32 {
-                            let x = px(pos.x as f64, self.zoom, self.center.x, WIDTH);
-                            let y = py(pos.y as f64, self.zoom, self.center.y, HEIGHT);
-                            ui.label(format!("pointer x: {}", x));
-                            ui.label(format!("pointer y: {}", -y));
+                    if let Some(pos) = ctx.pointer_hover_pos() {
+                        if view.contains(pos) {
+                            let (sx, sy) = ((pos.x * scale) as f64, (pos.y * scale) as f64);
+                            let offset = viewport.offset(sx, sy);
+                            let exact = viewport.at(sx, sy);
+                            ui.label(format!("pointer x: {}", exact.x));
+                            ui.label(format!("pointer y: {}", exact.y.neg()));
+                            let (x, y) = (center.x + offset.x, center.y + offset.y);
 
 
-                            let (iterations, points, period) =
-                                mandelcomplist(x, y, self.maxitr as f64, self.exponent);
+                            let formula = self.formula.build(self.exponent, &self.custom);
+                            let (iterations, points, period) = match self.julia {
+                                Some(c) => juliacomplist(&*formula, x, y, c, self.maxitr as f64),
+                                None => mandelcomplist(&*formula, x, y, self.maxitr as f64),
+                            };
+                            if self.preview && self.julia.is_none() {
+                                let c = Coord { x, y };
+                                if !matches!(self.preview_image, Some((old, _)) if old == c) {
+                                    let image = julia(
+                                        &Viewport::new(BigCoord::new(0., 0.), 1.5, PREVIEW, PREVIEW),
+                                        self.maxitr.min(250) as f64,
+                                        &*formula,
+                                        c,
+                                        Supersampling::OFF,
+                                        self.coloring,
+                                    );
+                                    self.preview_image = Some((
+                                        c,
+                                        RetainedImage::from_color_image(
+                                            "julia preview",
+                                            ColorImage::from_rgba_unmultiplied(
+                                                [PREVIEW as usize, PREVIEW as usize],
+                                                &image,
+                                            ),
+                                        ),
+                                    ));
+                                }
+                                if let Some((_, image)) = &self.preview_image {
+                                    // keep the thumbnail next to the pointer but inside the view
+                                    let size = PREVIEW as f32;
+                                    let min = Pos2 {
+                                        x: (pos.x + 16.).min(view.max.x - size),
+                                        y: (pos.y + 16.).min(view.max.y - size),
+                                    };
+                                    let rect = Rect::from_min_size(min, Vec2::splat(size));
+                                    painter.image(
+                                        image.texture_id(ctx),
+                                        rect,
+                                        Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                                        Color32::WHITE,
+                                    );
+                                    painter.rect_stroke(rect, 0., egui::Stroke { width: 1., color: Color32::WHITE });
+                                }
+                            }
                             if self.orbits{
                                 for point in points{
-                                    painter.circle_filled(Pos2{x: fractal::xp(point.x, self.center.x, self.zoom, WIDTH) as f32, y: fractal::yp(point.y, self.center.y, self.zoom, HEIGHT) as f32}, 2.0, Color32::WHITE);
+                                    painter.circle_filled(to_screen(point), 2.0, Color32::WHITE);
                                 }
                             }
                             if iterations == self.maxitr {
@@ -162,14 +419,35 @@ impl eframe::App for Content {
                 });
             });
 
+        if self.show_bookmarks {
+            egui::SidePanel::right("bookmarks").show(ctx, |ui| self.bookmarks(ui, ctx));
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal_wrapped(|ui| {
                 ui.style_mut().override_text_style = Some(egui::TextStyle::Heading);
                 ui.label(format!(
-                    "frame render time: {:.1}ms",
-                    (self.time / 1000000.)
+                    "frame render time: {:.1}ms ({})",
+                    (self.frame.time / 1000000.),
+                    self.frame.stats.precision.output()
                 ));
-                let fps = 1000000000. / (self.time);
+                if self.frame.stats.exhausted {
+                    ui.colored_label(Color32::LIGHT_RED, "out of precision, zoom out for a sharp image");
+                }
+                if self.frame.stats.skipped > 0 {
+                    ui.label(format!("{} iterations skipped", self.frame.stats.skipped));
+                }
+                let stats = self.frame.stats;
+                if stats.guessed > 0 {
+                    ui.label(format!(
+                        "{:.1}% guessed",
+                        stats.guessed as f64 * 100. / (self.frame.viewport.width * self.frame.viewport.height) as f64
+                    ));
+                }
+                if self.fill == Fill::Verify && self.frame.step == 1 {
+                    ui.label(format!("({} wrong)", stats.wrong));
+                }
+                let fps = 1000000000. / (self.frame.time);
                 let color = Color32::from_rgb(
                     (-(fps - 20.) * 255. / 5.) as u8,
                     ((fps - 10.) * 255. / 5.) as u8,
@@ -177,29 +455,181 @@ impl eframe::App for Content {
                 );
                 ui.colored_label(color, format!("({:.1} fps)", fps));
             });
+            if let Some(job) = &self.job {
+                ui.add(egui::ProgressBar::new(job.progress()).show_percentage());
+                // nothing else wakes the ui up while the bar moves
+                ctx.request_repaint_after(Duration::from_millis(50));
+            }
             ui.separator();
             ui.style_mut().spacing.item_spacing = Vec2 { x: 10., y: 15. };
+            ui.checkbox(&mut self.show_bookmarks, "bookmarks");
             ui.checkbox(&mut self.axes, "show axes");
             ui.checkbox(&mut self.orbits, "show orbits");
-            ui.add(egui::Slider::new(&mut self.maxitr, 0..=15000).text("max iterations"));
-            ui.add(egui::Slider::new(&mut self.exponent, 1..=100).text("exponent"));
+            ui.checkbox(&mut self.preview, "julia preview (shift + click to open)");
+            if let Some(c) = &mut self.julia {
+                ui.horizontal(|ui| {
+                    ui.label("julia set for c =");
+                    ui.add(egui::DragValue::new(&mut c.x).speed(0.001 * self.viewport.scale));
+                    // screen y points down, so the imaginary part is shown negated
+                    let mut im = -c.y;
+                    if ui.add(egui::DragValue::new(&mut im).speed(0.001 * self.viewport.scale).suffix("i")).changed() {
+                        c.y = -im;
+                    }
+                });
+                if ui.add(egui::Button::new("back to mandelbrot")).clicked() {
+                    self.julia = None;
+                    (self.viewport.center, self.viewport.scale) = self.parameter_view.clone();
+                }
+            }
             ui.add(
-                egui::DragValue::new(&mut self.center.x)
-                    .speed(0.1 * self.zoom)
-                    .prefix("x: "),
+                egui::Slider::new(&mut self.maxitr, 1..=1000000)
+                    .logarithmic(true)
+                    .text("max iterations"),
+            );
+            egui::ComboBox::from_label("solid regions")
+                .selected_text(self.fill.output())
+                .show_ui(ui, |ui| {
+                    for fill in Fill::ALL {
+                        ui.selectable_value(&mut self.fill, fill, fill.output());
+                    }
+                });
+            // extra samples per pixel, averaged, to smooth the edges
+            egui::ComboBox::from_label("supersampling")
+                .selected_text(self.supersampling.pattern.output())
+                .show_ui(ui, |ui| {
+                    for pattern in Pattern::ALL {
+                        ui.selectable_value(&mut self.supersampling.pattern, pattern, pattern.output());
+                    }
+                });
+            if self.supersampling.enabled() {
+                ui.add(egui::Slider::new(&mut self.supersampling.n, 2..=8).text("samples per side"));
+                ui.checkbox(&mut self.supersampling.adaptive, "only where neighbours differ");
+                if self.supersampling.adaptive {
+                    ui.add(
+                        egui::Slider::new(&mut self.supersampling.threshold, 0.01..=100.)
+                            .logarithmic(true)
+                            .text("difference (iterations)"),
+                    );
+                }
+            }
+            // only z^2 + c has a vector kernel, for comparing against the scalar one
+            egui::ComboBox::from_label("kernel")
+                .selected_text(self.simd.output())
+                .show_ui(ui, |ui| {
+                    for simd in Simd::ALL.into_iter().filter(|simd| simd.available()) {
+                        ui.selectable_value(&mut self.simd, simd, simd.output());
+                    }
+                });
+            egui::ComboBox::from_label("formula")
+                .selected_text(self.formula.output())
+                .show_ui(ui, |ui| {
+                    for kind in FormulaKind::ALL {
+                        ui.selectable_value(&mut self.formula, kind, kind.output());
+                    }
+                });
+            if self.formula == FormulaKind::Custom {
+                for (i, label) in ["z ->", "start z ="].iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(*label);
+                        if ui.text_edit_singleline(&mut self.custom_source[i]).changed() {
+                            match Program::compile(&self.custom_source[i]) {
+                                Ok(program) => {
+                                    if i == 0 {
+                                        self.custom.step = program;
+                                    } else {
+                                        self.custom.start = program;
+                                    }
+                                    self.custom_error[i] = None;
+                                }
+                                Err(e) => self.custom_error[i] = Some(e),
+                            }
+                        }
+                    });
+                    if let Some(e) = &self.custom_error[i] {
+                        ui.colored_label(Color32::LIGHT_RED, e.to_string());
+                    }
+                }
+                ui.add(
+                    egui::Slider::new(&mut self.custom.bailout, 2.0..=1000.)
+                        .logarithmic(true)
+                        .text("bailout radius"),
+                );
+                ui.label("functions: exp, log, sin, cos, tan, sinh, cosh, sqrt, conj, abs, re, im");
+            }
+            ui.add_enabled(
+                !self.animate,
+                egui::Slider::new(&mut self.exponent.re, -100.0..=100.).text("exponent"),
             );
             ui.add(
-                egui::DragValue::new(&mut self.center.y)
-                    .speed(0.1 * self.zoom)
-                    .prefix("y: "),
+                egui::DragValue::new(&mut self.exponent.im)
+                    .speed(0.01)
+                    .prefix("imaginary exponent: "),
             );
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.animate, "animate exponent from");
+                ui.add(egui::DragValue::new(&mut self.animation.0).clamp_range(-100..=100));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut self.animation.1).clamp_range(-100..=100));
+            });
+            if self.animate {
+                ui.add(
+                    egui::Slider::new(&mut self.animation.2, 0.01..=2.)
+                        .logarithmic(true)
+                        .text("cycles per second"),
+                );
+                // ease between the two integers and back so the ends linger a little
+                self.phase += ctx.input(|i| i.stable_dt) as f64 * self.animation.2;
+                let (from, to, _) = self.animation;
+                let t = 0.5 - 0.5 * (self.phase * std::f64::consts::TAU).cos();
+                self.exponent.re = from as f64 + (to - from) as f64 * t;
+                ctx.request_repaint();
+            }
+            // typed coordinates are exact decimals, y is shown as the imaginary part (up)
+            for (i, label) in ["x:", "y:"].iter().enumerate() {
+                let response = ui
+                    .horizontal(|ui| {
+                        ui.label(*label);
+                        ui.text_edit_singleline(&mut self.center_text[i])
+                    })
+                    .inner;
+                if response.lost_focus() {
+                    match self.center_text[i].parse::<Decimal>() {
+                        Ok(value) if i == 0 => {
+                            self.viewport.center.x = value;
+                            self.center_error = None;
+                        }
+                        Ok(value) => {
+                            self.viewport.center.y = value.neg();
+                            self.center_error = None;
+                        }
+                        Err(e) => self.center_error = Some(e),
+                    }
+                }
+                if !response.has_focus() {
+                    self.center_text[i] = match i {
+                        0 => self.viewport.center.x.to_string(),
+                        _ => self.viewport.center.y.neg().to_string(),
+                    };
+                }
+            }
+            if let Some(e) = &self.center_error {
+                ui.colored_label(Color32::LIGHT_RED, e);
+            }
             ui.add(
-                egui::Slider::new(&mut self.zoom, 1.0..=0.000000000001)
+                egui::Slider::new(&mut self.viewport.scale, 1.0..=1e-290)
                     .logarithmic(true)
                     .text("scale"),
             );
+            // kept in radians, shown in degrees
+            let mut degrees = self.viewport.rotation.to_degrees();
+            if ui
+                .add(egui::Slider::new(&mut degrees, -180.0..=180.).suffix("°").text("rotation (alt + scroll)"))
+                .changed()
+            {
+                self.viewport.rotation = degrees.to_radians();
+            }
             egui::ComboBox::from_label("Select one!")
-                .selected_text(format!("{}", self.coloring.output()))
+                .selected_text(self.coloring.output())
                 .show_ui(ui, |ui| {
                     ui.selectable_value(&mut self.coloring, ColoringMode::Hsl(0., 1., 360.), "Hsl");
                     ui.selectable_value(
@@ -230,41 +660,97 @@ impl eframe::App for Content {
                 let _ = ui.add(egui::Slider::new(range, 0.0..=500.).text("colour normalisation"));
             };
             if ui.add(egui::Button::new("reset")).clicked() {
+                // only the view goes back to the start, the bookmarks and a running export stay
                 *self = Self {
-                    center: Coord { x: -0.765, y: 0. },
-                    zoom: 1.,
-                    image: RetainedImage::from_color_image(
-                        "mandel",
-                        ColorImage::from_rgba_unmultiplied(
-                            [WIDTH as usize, HEIGHT as usize],
-                            &mandelbrot(
-                                Coord { x: -0.765, y: 0. },
-                                1.,
-                                300.,
-                                2,
-                                WIDTH,
-                                HEIGHT,
-                                ColoringMode::Hsl(0., 1., 360.),
-                            ),
-                        ),
-                    ),
-                    time: 50000000.,
-                    maxitr: 300,
-                    exponent: 2,
-                    prev: (
-                        Coord { x: -0.765, y: 0. },
-                        1.,
-                        0,
-                        2,
-                        ColoringMode::Hsl(0., 1., 360.),
-                        false,
-                    ),
-                    coloring: ColoringMode::Hsl(0., 1., 360.),
-                    pi: 0.,
-                    axes: false,
-                    orbits: false,
-                }
+                    bookmarks: std::mem::take(&mut self.bookmarks),
+                    bookmarks_path: std::mem::take(&mut self.bookmarks_path),
+                    export: self.export.take(),
+                    ..Self::default()
+                };
+            }
+            ui.collapsing("session", |ui| {
+                ui.text_edit_singleline(&mut self.session_path);
+                ui.horizontal(|ui| {
+                    let path = PathBuf::from(&self.session_path);
+                    if ui.add(egui::Button::new("save")).clicked() {
+                        let saved = session::write(&path, &self.fields());
+                        self.session = Some(saved.map(|_| (format!("saved {}", path.display()), Vec::new())));
+                    }
+                    if ui.add(egui::Button::new("open")).clicked() {
+                        self.session = Some(self.resume(&path));
+                    }
+                });
+                status(ui, &self.session);
+            });
+            ui.collapsing("open exported png (or drop one on the window)", |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.open_path);
+                    if ui.add(egui::Button::new("open")).clicked() {
+                        self.open(PathBuf::from(&self.open_path));
+                    }
+                    if ui.add(egui::Button::new("browse")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("png", &["png"]).pick_file() {
+                            self.open_path = path.display().to_string();
+                            self.open(path);
+                        }
+                    }
+                });
+                status(ui, &self.restored);
+            });
+            if let Some(path) = ctx.input(|i| i.raw.dropped_files.first().and_then(|file| file.path.clone())) {
+                self.open(path);
+            }
+            if let Some(result) = self.export.as_ref().and_then(|export| export.poll()) {
+                self.export_result = Some(result);
+                self.export = None;
             }
+            ui.collapsing("export png", |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.export_size[0]).clamp_range(1..=65535).suffix(" px"));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut self.export_size[1]).clamp_range(1..=65535).suffix(" px"));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("save to");
+                    ui.text_edit_singleline(&mut self.export_path);
+                });
+                ui.checkbox(&mut self.export_tiled, "tiled poster (resumes from tiles left by a cancelled one)");
+                if let Some(export) = &self.export {
+                    ui.add(egui::ProgressBar::new(export.progress()).show_percentage());
+                    if let Some((done, total, eta)) = export.tiles() {
+                        let eta = match eta {
+                            Some(eta) => format!(", about {}m {}s left", eta.as_secs() / 60, eta.as_secs() % 60),
+                            None => String::new(),
+                        };
+                        ui.label(format!("{} of {} tiles{}", done, total, eta));
+                    }
+                    ctx.request_repaint_after(Duration::from_millis(50));
+                    if ui.add(egui::Button::new("cancel")).clicked() {
+                        self.export = None;
+                    }
+                } else if ui.add(egui::Button::new("export")).clicked() {
+                    let [width, height] = self.export_size;
+                    let viewport = Viewport { width, height, ..self.viewport.clone() };
+                    self.export = Some(Export::spawn(
+                        viewport,
+                        self.maxitr as f64,
+                        self.formula.build(self.exponent, &self.custom),
+                        self.julia,
+                        self.supersampling,
+                        self.coloring,
+                        self.export_path.clone(),
+                        self.metadata(),
+                        self.export_tiled,
+                        ctx.clone(),
+                    ));
+                    self.export_result = None;
+                }
+                match &self.export_result {
+                    Some(Ok(path)) => ui.label(format!("saved {}", path)),
+                    Some(Err(e)) => ui.colored_label(Color32::LIGHT_RED, e),
+                    None => ui.label(""),
+                };
+            });
             if ui.add(egui::Button::new("calculate pi!")).clicked() {
                 self.pi = crate::fractal::piapprox();
             }
@@ -272,95 +758,639 @@ impl eframe::App for Content {
                 ui.label(format!("pi = {}", self.pi));
             }
 
-            let new: (Coord, f64, i32, i32, ColoringMode, bool) = (
-                self.center,
-                self.zoom,
-                self.maxitr,
-                self.exponent,
-                self.coloring,
-                self.axes,
-            );
-            if new != self.prev {
-                self.render();
+            if self.prev != Some(self.view()) {
+                self.render(ctx);
+            } else if self.coloring != self.prev_coloring {
+                self.recolor();
+            }
+            if let Some(frame) = self.job.as_ref().and_then(|job| job.poll()) {
+                if frame.complete {
+                    self.job = None;
+                    if let Some(view) = self.prev.clone() {
+                        self.finished = Some((view, frame.samples.clone(), frame.subsamples.clone()));
+                    }
+                }
+                self.frame = frame;
+                self.recolor();
             }
+            // pointer positions are in points, the render works in physical pixels
+            let view = self.rect();
+            let scale = self.pixels_per_point;
             if ctx.input(|i| i.pointer.is_decidedly_dragging()) {
-                let origin = ctx.input(|i| i.pointer.press_origin());
-                if origin.is_some() {
-                    let origin = origin.unwrap();
+                if let Some(origin) = ctx.input(|i| i.pointer.press_origin()) {
                     let current = ctx.input(|i| i.pointer.interact_pos()).unwrap();
-                    if origin.x < WIDTH as f32
-                        && origin.y < HEIGHT as f32
-                        && current.x < WIDTH as f32
-                        && current.y < HEIGHT as f32
+                    if view.contains(origin)
+                        && view.contains(current)
                         && origin.y > 0.
                         && current.y > 0.
                     {
-                        let dy = ctx.input(|i| i.pointer.delta()).y as f64;
-                        let dx = ctx.input(|i| i.pointer.delta()).x as f64;
-                        self.center.x -= ((2.47) / WIDTH as f64) * self.zoom * dx;
-                        self.center.y -= ((2.24) / HEIGHT as f64) * self.zoom * dy;
-                        self.render()
+                        self.drag += ctx.input(|i| i.pointer.delta()) * scale;
+                        let whole = self.drag.round();
+                        self.drag -= whole;
+                        if whole != Vec2::ZERO {
+                            self.viewport.pan(-whole.x as f64, -whole.y as f64);
+                            self.render(ctx)
+                        }
                     }
                 }
             }
+            // a tenth of the scale, in pixels
+            let step = 0.1 * self.viewport.scale / self.viewport.pixel();
             if ctx.input(|i| i.key_pressed(Key::A)) {
-                self.center.x -= 0.1 * self.zoom;
-                self.render();
+                self.viewport.pan(-step, 0.);
+                self.render(ctx);
             }
             if ctx.input(|i| i.key_pressed(Key::W)) {
-                self.center.y -= 0.1 * self.zoom;
-                self.render();
+                self.viewport.pan(0., -step);
+                self.render(ctx);
             }
             if ctx.input(|i| i.key_pressed(Key::S)) {
-                self.center.y += 0.1 * self.zoom;
-                self.render();
+                self.viewport.pan(0., step);
+                self.render(ctx);
             }
             if ctx.input(|i| i.key_pressed(Key::D)) {
-                self.center.x += 0.1 * self.zoom;
-                self.render();
-            }
-            let current = ctx.input(|i| i.pointer.hover_pos());
-            if current.is_some() {
-                let current = current.unwrap();
-                if current.x < WIDTH as f32 && current.y < HEIGHT as f32 && current.y > 0. {
-                    if ctx.input(|i| i.scroll_delta.y > 0.) {
-                        self.zoom *= 0.5;
-                        self.render();
+                self.viewport.pan(step, 0.);
+                self.render(ctx);
+            }
+            if let Some(current) = ctx.input(|i| i.pointer.hover_pos()) {
+                if view.contains(current) && current.y > 0. {
+                    let (x, y) = ((current.x * scale) as f64, (current.y * scale) as f64);
+                    let (scroll, alt) = ctx.input(|i| (i.scroll_delta.y, i.modifiers.alt));
+                    if alt && scroll != 0. {
+                        // turn about the middle of the view, one step a notch
+                        self.viewport.rotate(ROTATE_STEP.to_radians() * scroll.signum() as f64);
+                        self.render(ctx);
+                    } else if scroll > 0. {
+                        self.viewport.scale = (self.viewport.scale * 0.5).max(1e-290);
+                        self.render(ctx);
+                    } else if scroll < 0. {
+                        self.viewport.scale *= 2.;
+                        self.render(ctx);
                     }
-                    if ctx.input(|i| i.scroll_delta.y < 0.) {
-                        self.zoom *= 2.;
-                        self.render();
+                    if self.julia.is_none()
+                        && ctx.input(|i| i.pointer.primary_clicked() && i.modifiers.shift)
+                    {
+                        let center = self.viewport.center.to_coord();
+                        let offset = self.viewport.offset(x, y);
+                        self.julia = Some(Coord {
+                            x: center.x + offset.x,
+                            y: center.y + offset.y,
+                        });
+                        self.parameter_view = (self.viewport.center.clone(), self.viewport.scale);
+                        self.viewport.center = BigCoord::new(0., 0.);
+                        self.viewport.scale = 1.5;
                     }
                     if ctx.input(|i| i.pointer.secondary_pressed()) {
-                        self.center = Coord {
-                            x: px(current.x as f64, self.zoom, self.center.x, WIDTH),
-                            y: py(current.y as f64, self.zoom, self.center.y, HEIGHT),
-                        }
+                        self.viewport.center = self.viewport.at(x, y);
                     }
                 }
             }
-            self.prev = new;
         });
     }
+    // keep the session for next time, a failed save isn't worth holding up closing for
+    fn on_close_event(&mut self) -> bool {
+        if let Some(path) = session::last() {
+            let _ = session::write(&path, &self.fields());
+        }
+        return true;
+    }
 }
 impl Content {
-    fn render(&mut self) {
-        let now = Instant::now();
+    // the defaults, then whatever the last session left
+    fn startup() -> Content {
+        let mut content = Content::default();
+        if let Some(path) = session::last().filter(|path| path.exists()) {
+            content.session = Some(content.resume(&path));
+        }
+        if let Some(path) = bookmarks::saved().filter(|path| path.exists()) {
+            match bookmarks::read(&path) {
+                Ok(list) => content.bookmarks = list,
+                Err(e) => content.bookmarks_status = Some(Err(e)),
+            }
+        }
+        return content;
+    }
+    // the bookmarks panel: add the current view, jump to or delete one, share the list
+    fn bookmarks(&mut self, ui: &mut Ui, ctx: &egui::Context) {
+        ui.heading("bookmarks");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.bookmark_name);
+            if ui.add(egui::Button::new("add current view")).clicked() {
+                let mut fields = self.metadata();
+                fields.retain(|(key, _)| key != "Software");
+                self.bookmarks.push(Bookmark {
+                    name: std::mem::take(&mut self.bookmark_name),
+                    fields,
+                    thumbnail: None,
+                    rendering: None,
+                });
+                self.keep_bookmarks();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.bookmarks_path);
+            let path = PathBuf::from(&self.bookmarks_path);
+            if ui.add(egui::Button::new("import")).clicked() {
+                self.bookmarks_status = Some(bookmarks::read(&path).map(|list| {
+                    let done = format!("imported {} bookmarks from {}", list.len(), path.display());
+                    self.bookmarks.extend(list);
+                    (done, Vec::new())
+                }));
+                self.keep_bookmarks();
+            }
+            if ui.add(egui::Button::new("export")).clicked() {
+                let written = bookmarks::write(&path, &self.bookmarks);
+                self.bookmarks_status = Some(written.map(|_| (format!("exported to {}", path.display()), Vec::new())));
+            }
+        });
+        status(ui, &self.bookmarks_status);
+        ui.separator();
+        // one thumbnail at a time on a background job, a deep bookmark can take seconds
+        if !self.bookmarks.iter().any(|bookmark| bookmark.rendering.is_some()) {
+            let waiting = self.bookmarks.iter_mut().find(|bookmark| bookmark.thumbnail.is_none());
+            if let Some(bookmark) = waiting {
+                bookmark.rendering = Some(Content::thumbnail(&bookmark.fields, ctx));
+            }
+        }
+        for bookmark in &mut self.bookmarks {
+            let Some((job, coloring)) = &bookmark.rendering else { continue };
+            // each pass replaces the last, so it sharpens as it goes
+            if let Some(frame) = job.poll() {
+                let rgba = colorize(&frame.samples, &frame.subsamples, frame.maxitr, *coloring);
+                let size = [THUMBNAIL as usize, THUMBNAIL as usize];
+                let image = ColorImage::from_rgba_unmultiplied(size, &rgba);
+                bookmark.thumbnail = Some(RetainedImage::from_color_image("thumbnail", image));
+                if frame.complete {
+                    bookmark.rendering = None;
+                }
+            }
+        }
+        let mut jump = None;
+        let mut delete = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, bookmark) in self.bookmarks.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let size = Vec2::splat(THUMBNAIL as f32);
+                    let clicked = match &bookmark.thumbnail {
+                        Some(image) => ui.add(egui::ImageButton::new(image.texture_id(ctx), size)).clicked(),
+                        None => ui.add_sized(size, egui::Button::new("...")).clicked(),
+                    };
+                    if clicked || ui.link(&bookmark.name).clicked() {
+                        jump = Some(i);
+                    }
+                    if ui.small_button("delete").clicked() {
+                        delete = Some(i);
+                    }
+                });
+            }
+        });
+        if let Some(i) = jump {
+            let fields = self.bookmarks[i].fields.clone();
+            let problems = self.restore(&fields);
+            self.bookmarks_status = Some(Ok((format!("jumped to {}", self.bookmarks[i].name), problems)));
+        }
+        if let Some(i) = delete {
+            self.bookmarks.remove(i);
+            self.keep_bookmarks();
+        }
+    }
+    // saved after every change, not just on exit, so a crash doesn't lose any
+    fn keep_bookmarks(&mut self) {
+        if let Some(path) = bookmarks::saved() {
+            if let Err(e) = bookmarks::write(&path, &self.bookmarks) {
+                self.bookmarks_status = Some(Err(e));
+            }
+        }
+    }
+    // a small render of a bookmarked view, whatever fields it has on top of the defaults,
+    // and the colouring it was saved with
+    fn thumbnail(fields: &[(String, String)], ctx: &egui::Context) -> (Job, ColoringMode) {
+        let mut content = Content::default();
+        content.restore(fields);
+        let viewport = Viewport {
+            width: THUMBNAIL,
+            height: THUMBNAIL,
+            ..content.viewport.clone()
+        };
+        let job = Job::spawn(
+            viewport,
+            content.maxitr as f64,
+            content.formula.build(content.exponent, &content.custom),
+            content.julia,
+            None,
+            Fill::Exact,
+            Simd::best(),
+            Supersampling::OFF,
+            ctx.clone(),
+        );
+        return (job, content.coloring);
+    }
+    // everything worth keeping between runs: the view as exported, plus the settings around it
+    fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = self.metadata();
+        // the custom formula is kept whichever one is selected, so switching away doesn't lose it
+        fields.retain(|(key, _)| !["Software", "step", "start", "bailout"].contains(&key.as_str()));
+        fields.extend(self.custom());
+        let supersampling = self.supersampling;
+        fields.extend([
+            (String::from("axes"), self.axes.to_string()),
+            (String::from("orbits"), self.orbits.to_string()),
+            (String::from("preview"), self.preview.to_string()),
+            (String::from("fill"), self.fill.output()),
+            (String::from("kernel"), self.simd.output()),
+            (String::from("supersampling"), supersampling.pattern.output()),
+            (String::from("samples"), supersampling.n.to_string()),
+            (String::from("adaptive"), supersampling.adaptive.to_string()),
+            (String::from("threshold"), supersampling.threshold.to_string()),
+            (String::from("animate"), self.animate.to_string()),
+            (String::from("animation"), format!("{} {} {}", self.animation.0, self.animation.1, self.animation.2)),
+        ]);
+        return fields;
+    }
+    // a saved session, reporting what couldn't be restored like an image would
+    fn resume(&mut self, path: &Path) -> Result<(String, Vec<String>), String> {
+        let fields = session::read(path)?;
+        let mut problems = self.restore(&fields);
+        if self.formula != FormulaKind::Custom {
+            self.restore_custom(&fields, &mut problems);
+        }
+        let unreadable = |key: &str, value: &str| format!("{} `{}` is unreadable", key, value);
+        for (key, flag) in [("axes", &mut self.axes), ("orbits", &mut self.orbits), ("preview", &mut self.preview)] {
+            if let Some(value) = field(&fields, key, &mut problems) {
+                match value.parse() {
+                    Ok(value) => *flag = value,
+                    Err(_) => problems.push(unreadable(key, value)),
+                }
+            }
+        }
+        if let Some(fill) = field(&fields, "fill", &mut problems) {
+            match Fill::ALL.into_iter().find(|kind| kind.output() == fill) {
+                Some(kind) => self.fill = kind,
+                None => problems.push(unreadable("fill", fill)),
+            }
+        }
+        if let Some(simd) = field(&fields, "kernel", &mut problems) {
+            match Simd::ALL.into_iter().find(|kind| kind.output() == simd) {
+                Some(kind) if kind.available() => self.simd = kind,
+                Some(_) => problems.push(format!("kernel `{}` isn't available on this cpu", simd)),
+                None => problems.push(unreadable("kernel", simd)),
+            }
+        }
+        if let Some(pattern) = field(&fields, "supersampling", &mut problems) {
+            match Pattern::ALL.into_iter().find(|kind| kind.output() == pattern) {
+                Some(kind) => self.supersampling.pattern = kind,
+                None => problems.push(unreadable("supersampling", pattern)),
+            }
+        }
+        if let Some(n) = field(&fields, "samples", &mut problems) {
+            match n.parse() {
+                Ok(n @ 2..=8) => self.supersampling.n = n,
+                _ => problems.push(unreadable("samples", n)),
+            }
+        }
+        if let Some(adaptive) = field(&fields, "adaptive", &mut problems) {
+            match adaptive.parse() {
+                Ok(adaptive) => self.supersampling.adaptive = adaptive,
+                Err(_) => problems.push(unreadable("adaptive", adaptive)),
+            }
+        }
+        if let Some(threshold) = field(&fields, "threshold", &mut problems) {
+            match threshold.parse::<f64>() {
+                Ok(threshold) if threshold > 0. => self.supersampling.threshold = threshold,
+                _ => problems.push(unreadable("threshold", threshold)),
+            }
+        }
+        if let Some(animate) = field(&fields, "animate", &mut problems) {
+            match animate.parse() {
+                Ok(animate) => self.animate = animate,
+                Err(_) => problems.push(unreadable("animate", animate)),
+            }
+        }
+        if let Some(animation) = field(&fields, "animation", &mut problems) {
+            // from, to and speed
+            let words: Vec<&str> = animation.split_whitespace().collect();
+            match words[..] {
+                [from, to, speed] => match (from.parse(), to.parse(), speed.parse()) {
+                    (Ok(from), Ok(to), Ok(speed)) => self.animation = (from, to, speed),
+                    _ => problems.push(unreadable("animation", animation)),
+                },
+                _ => problems.push(unreadable("animation", animation)),
+            }
+        }
+        return Ok((format!("restored {}", path.display()), problems));
+    }
+    fn view(&self) -> View {
+        (
+            self.viewport.clone(),
+            self.maxitr,
+            self.exponent,
+            self.julia,
+            self.formula,
+            self.custom.clone(),
+            self.fill,
+            self.simd,
+            self.supersampling,
+        )
+    }
+    // the render parameters, as png text chunks, enough to get back to this view
+    // y is flipped back to pointing up, like everywhere else it's shown
+    fn metadata(&self) -> Vec<(String, String)> {
+        let mut text = vec![
+            (String::from("Software"), String::from("multibrot-explorer")),
+            (String::from("center_x"), self.viewport.center.x.to_string()),
+            (String::from("center_y"), self.viewport.center.y.neg().to_string()),
+            (String::from("zoom"), self.viewport.scale.to_string()),
+            (String::from("rotation"), self.viewport.rotation.to_degrees().to_string()),
+            (String::from("maxitr"), self.maxitr.to_string()),
+            (String::from("exponent"), format!("{}{:+}i", self.exponent.re, self.exponent.im)),
+            (String::from("formula"), self.formula.output()),
+            (String::from("coloring"), self.coloring.to_string()),
+        ];
+        if self.formula == FormulaKind::Custom {
+            text.extend(self.custom());
+        }
+        if let Some(c) = self.julia {
+            text.push((String::from("julia"), format!("{}{:+}i", c.x, -c.y)));
+        }
+        return text;
+    }
+    // the view saved in an exported image, as far as its text chunks allow
+    fn open(&mut self, path: PathBuf) {
+        self.restored = Some(read_text(&path).and_then(|text| {
+            if !text.iter().any(|(key, _)| key == "center_x") {
+                return Err(format!("{} has no view saved in it", path.display()));
+            }
+            Ok((format!("restored the view from {}", path.display()), self.restore(&text)))
+        }));
+    }
+    // everything metadata wrote, each field on its own so one bad value doesn't lose the rest
+    // returns what was missing or unreadable, those keep their current values
+    fn restore(&mut self, text: &[(String, String)]) -> Vec<String> {
+        let mut problems = Vec::new();
+        let unreadable = |key: &str, value: &str| format!("{} `{}` is unreadable", key, value);
+        if let Some(x) = field(text, "center_x", &mut problems) {
+            match x.parse::<Decimal>() {
+                Ok(x) => self.viewport.center.x = x,
+                Err(_) => problems.push(unreadable("center_x", x)),
+            }
+        }
+        if let Some(y) = field(text, "center_y", &mut problems) {
+            match y.parse::<Decimal>() {
+                Ok(y) => self.viewport.center.y = y.neg(),
+                Err(_) => problems.push(unreadable("center_y", y)),
+            }
+        }
+        if let Some(zoom) = field(text, "zoom", &mut problems) {
+            match zoom.parse::<f64>() {
+                Ok(scale) if scale > 0. => self.viewport.scale = scale,
+                _ => problems.push(unreadable("zoom", zoom)),
+            }
+        }
+        if let Some(rotation) = field(text, "rotation", &mut problems) {
+            match rotation.parse::<f64>() {
+                Ok(degrees) if degrees.is_finite() => self.viewport.rotation = degrees.to_radians(),
+                _ => problems.push(unreadable("rotation", rotation)),
+            }
+        }
+        if let Some(maxitr) = field(text, "maxitr", &mut problems) {
+            match maxitr.parse::<i32>() {
+                Ok(n) if n > 0 => self.maxitr = n,
+                _ => problems.push(unreadable("maxitr", maxitr)),
+            }
+        }
+        if let Some(exponent) = field(text, "exponent", &mut problems) {
+            match parse_complex(exponent) {
+                Some(n) => self.exponent = n,
+                None => problems.push(unreadable("exponent", exponent)),
+            }
+        }
+        if let Some(formula) = field(text, "formula", &mut problems) {
+            match FormulaKind::ALL.into_iter().find(|kind| kind.output() == formula) {
+                Some(kind) => self.formula = kind,
+                None => problems.push(unreadable("formula", formula)),
+            }
+        }
+        if let Some(coloring) = field(text, "coloring", &mut problems) {
+            match coloring.parse::<ColoringMode>() {
+                Ok(mode) => self.coloring = mode,
+                Err(e) => problems.push(format!("coloring: {}", e)),
+            }
+        }
+        // only written for custom formulas
+        if self.formula == FormulaKind::Custom {
+            self.restore_custom(text, &mut problems);
+        }
+        // and julia only for julia sets, so it being missing isn't a problem
+        self.julia = None;
+        if let Some((_, julia)) = text.iter().find(|(key, _)| key == "julia") {
+            match parse_complex(julia) {
+                Some(c) => {
+                    self.julia = Some(Coord { x: c.re, y: -c.im });
+                    // the view it was opened from isn't saved, so going back shows the area around c
+                    self.parameter_view = (BigCoord::new(c.re, -c.im), PARAMETER_SCALE);
+                }
+                None => problems.push(unreadable("julia", julia)),
+            }
+        }
+        return problems;
+    }
+    // the custom formula's step, start and bailout
+    fn custom(&self) -> [(String, String); 3] {
+        return [
+            (String::from("step"), self.custom_source[0].clone()),
+            (String::from("start"), self.custom_source[1].clone()),
+            (String::from("bailout"), self.custom.bailout.to_string()),
+        ];
+    }
+    fn restore_custom(&mut self, text: &[(String, String)], problems: &mut Vec<String>) {
+        for (i, key) in ["step", "start"].iter().enumerate() {
+            let Some(source) = field(text, key, problems) else { continue };
+            match Program::compile(source) {
+                Ok(program) if i == 0 => self.custom.step = program,
+                Ok(program) => self.custom.start = program,
+                Err(e) => problems.push(format!("{} `{}`: {}", key, source, e)),
+            }
+            self.custom_source[i] = source.to_string();
+        }
+        if let Some(bailout) = field(text, "bailout", problems) {
+            match bailout.parse::<f64>() {
+                Ok(radius) if radius > 0. && radius.is_finite() => self.custom.bailout = radius,
+                _ => problems.push(format!("bailout `{}` is unreadable", bailout)),
+            }
+        }
+    }
+    // the last complete frame's samples moved to the current center, if it's only been panned by whole pixels
+    fn reuse(&self) -> Option<Reuse> {
+        let (finished, finished_samples, finished_subsamples) = self.finished.as_ref()?;
+        let mut view = self.view();
+        view.0.center = finished.0.center.clone();
+        if *finished != view {
+            return None;
+        }
+        let viewport = &self.viewport;
+        let (x, y) = viewport.screen(viewport.center.difference(&finished.0.center));
+        let (x, y) = (x - viewport.width as f64 / 2., y - viewport.height as f64 / 2.);
+        // offset() rounds to a few digits below the pixel size, so allow a little slack
+        if (x - x.round()).abs() > 0.01 || (y - y.round()).abs() > 0.01 {
+            return None;
+        }
+        if x.abs() >= viewport.width as f64 || y.abs() >= viewport.height as f64 {
+            return None;
+        }
+        let (width, height) = (viewport.width, viewport.height);
+        let (dx, dy) = (x.round() as i32, y.round() as i32);
+        let (samples, known) = shift(finished_samples, Sample { iterations: 0, r: 0. }, width, height, dx, dy);
+        // frames rendered without supersampling have no subsamples at all
+        let subsamples = if finished_subsamples.is_empty() {
+            vec![Vec::new(); samples.len()]
+        } else {
+            shift(finished_subsamples, Vec::new(), width, height, dx, dy).0
+        };
+        return Some((samples, subsamples, known));
+    }
+    // replacing the job cancels whatever was still rendering
+    fn render(&mut self, ctx: &egui::Context) {
+        let reuse = self.reuse();
+        self.job = Some(Job::spawn(
+            self.viewport.clone(),
+            self.maxitr as f64,
+            self.formula.build(self.exponent, &self.custom),
+            self.julia,
+            reuse,
+            self.fill,
+            self.simd,
+            self.supersampling,
+            ctx.clone(),
+        ));
+        self.prev = Some(self.view());
+    }
+    // only the colouring changed, so reuse the iteration counts
+    fn recolor(&mut self) {
+        if self.frame.samples.is_empty() {
+            return;
+        }
+        let viewport = &self.frame.viewport;
         self.image = RetainedImage::from_color_image(
             "mandel",
             ColorImage::from_rgba_unmultiplied(
-                [WIDTH as usize, HEIGHT as usize],
-                &mandelbrot(
-                    self.center,
-                    self.zoom,
-                    self.maxitr as f64,
-                    self.exponent,
-                    WIDTH,
-                    HEIGHT,
-                    self.coloring,
-                ),
+                [viewport.width as usize, viewport.height as usize],
+                &colorize(&self.frame.samples, &self.frame.subsamples, self.frame.maxitr, self.coloring),
             ),
         );
-        self.time = now.elapsed().as_nanos() as f64;
+        self.prev_coloring = self.coloring;
+    }
+    // the view in points, at the top left of the window
+    fn rect(&self) -> Rect {
+        let size = Vec2::new(self.viewport.width as f32, self.viewport.height as f32) / self.pixels_per_point;
+        Rect::from_min_size(Pos2::ZERO, size)
+    }
+    // where the last finished frame's corners land in the current view, clockwise from the top left
+    fn placement(&self) -> [Pos2; 4] {
+        let (old, new) = (&self.frame.viewport, &self.viewport);
+        let shift = old.center.difference(&new.center);
+        let (w, h) = (old.width as f64, old.height as f64);
+        return [(0., 0.), (w, 0.), (w, h), (0., h)].map(|(x, y)| {
+            let offset = old.offset(x, y);
+            let (x, y) = new.screen(Coord { x: shift.x + offset.x, y: shift.y + offset.y });
+            pos2(x as f32 / self.pixels_per_point, y as f32 / self.pixels_per_point)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a complete frame of the current view whose every sample says which pixel it came from
+    fn finished(content: &mut Content, subsamples: bool) {
+        content.viewport.width = 16;
+        content.viewport.height = 12;
+        let samples: Vec<Sample> = (0..16 * 12).map(|i| Sample { iterations: i, r: 0. }).collect();
+        let subsamples = match subsamples {
+            true => samples.iter().map(|sample| vec![*sample; 4]).collect(),
+            false => Vec::new(),
+        };
+        content.finished = Some((content.view(), samples, subsamples));
+    }
+
+    #[test]
+    fn pan_without_supersampling() {
+        let mut content = Content::default();
+        finished(&mut content, false);
+        content.viewport.pan(3., -2.);
+        let (samples, subsamples, known) = content.reuse().unwrap();
+        assert_eq!(subsamples.len(), 16 * 12);
+        assert!(subsamples.iter().all(|subsamples| subsamples.is_empty()));
+        for y in 0..12 {
+            for x in 0..16 {
+                let i = y * 16 + x;
+                let (ox, oy) = (x as i32 + 3, y as i32 - 2);
+                assert_eq!(known[i], ox < 16 && oy >= 0, "({}, {})", x, y);
+                if known[i] {
+                    assert_eq!(samples[i].iterations, oy * 16 + ox);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pan_with_supersampling() {
+        let mut content = Content::default();
+        finished(&mut content, true);
+        content.viewport.pan(-1., 4.);
+        let (samples, subsamples, known) = content.reuse().unwrap();
+        for i in 0..16 * 12 {
+            match known[i] {
+                true => assert_eq!(subsamples[i], vec![samples[i]; 4]),
+                false => assert!(subsamples[i].is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_to_reuse() {
+        let mut content = Content::default();
+        finished(&mut content, false);
+        // off the frame entirely
+        content.viewport.pan(16., 0.);
+        assert!(content.reuse().is_none());
+        // or anything but a pan
+        let mut content = Content::default();
+        finished(&mut content, false);
+        content.maxitr += 1;
+        assert!(content.reuse().is_none());
+    }
+
+    #[test]
+    fn complex_numbers_as_exported() {
+        for (re, im) in [(-1.5, -2.), (-0.75, -0.), (3e20, -7.5e-8)] {
+            let written = format!("{}{:+}i", re, im);
+            assert_eq!(parse_complex(&written), Some(Complex { re, im }), "{}", written);
+        }
+        // a sign inside an exponent doesn't split the parts
+        assert_eq!(parse_complex("1e-5+2E+3i"), Some(Complex { re: 1e-5, im: 2e3 }));
+        assert_eq!(parse_complex("1e+5i"), None);
+    }
+
+    #[test]
+    fn coloring_modes_as_exported() {
+        let tint = ColoringMode::Monochrome(Color32::from_rgb(0x12, 0xab, 0xff), 2.25);
+        assert_eq!(tint.to_string(), "monochrome #12abff 2.25");
+        assert_eq!(tint.to_string().parse::<ColoringMode>(), Ok(tint));
+        assert_eq!("hsl 12.5 1 360".parse::<ColoringMode>(), Ok(ColoringMode::Hsl(12.5, 1., 360.)));
+        assert!("monochrome #12345 1".parse::<ColoringMode>().is_err());
+    }
+
+    // a file in the temp directory, removed when dropped, even if the test fails first
+    pub struct TempFile(pub PathBuf);
+    impl TempFile {
+        pub fn new(name: &str) -> TempFile {
+            return TempFile(std::env::temp_dir().join(format!("multibrot-{}-{}", std::process::id(), name)));
+        }
+    }
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
     }
 }
@@ -0,0 +1,172 @@
+use crate::fractal::{cadd, cmul, csub, norm, Complex, Progress};
+use crate::precision::{BigComplex, BigCoord, Real};
+use std::sync::atomic::Ordering;
+
+// deep zoom: one high precision reference orbit Z at the view center, every pixel then
+// only iterates its f64 offset from it, d -> (Z + d)^n - Z^n + dc
+// which stays accurate however small dc gets (down to f64's exponent range)
+
+pub struct Reference {
+    orbit: Vec<Complex>,
+    degree: i32,
+    // binomial coefficients n choose j
+    binomial: Vec<f64>,
+}
+impl Reference {
+    // None if cancelled part way, at deep zoom the orbit alone can take seconds
+    pub fn new(center: &BigCoord, scale: f64, degree: i32, maxitr: f64, progress: &Progress) -> Option<Reference> {
+        // enough fractional bits for the pixel spacing plus some headroom
+        let bits = (80. - scale.log2()).max(64.) as u32;
+        let c = BigComplex {
+            re: center.x.to_real(bits),
+            im: center.y.to_real(bits),
+        };
+        let mut z = BigComplex {
+            re: Real::zero(bits),
+            im: Real::zero(bits),
+        };
+        let mut orbit = vec![Complex { re: 0., im: 0. }];
+        while (orbit.len() as f64) < maxitr {
+            if progress.cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            z = z.powi(degree).add(&c);
+            let point = Complex {
+                re: z.re.to_f64(),
+                im: z.im.to_f64(),
+            };
+            orbit.push(point);
+            if norm(point) >= 4. {
+                break;
+            }
+        }
+        let mut binomial = vec![1.];
+        for j in 1..=degree {
+            binomial.push(binomial[j as usize - 1] * (degree - j + 1) as f64 / j as f64);
+        }
+        Some(Reference {
+            orbit,
+            degree,
+            binomial,
+        })
+    }
+    // (Z + d)^n - Z^n + dc, expanded so nothing cancels
+    fn delta(&self, z: Complex, d: Complex, dc: Complex) -> Complex {
+        if self.degree == 2 {
+            let twoz = Complex {
+                re: z.re + z.re + d.re,
+                im: z.im + z.im + d.im,
+            };
+            return cadd(cmul(d, twoz), dc);
+        }
+        // horner in d: sum over j of (n choose j) Z^(n - j) d^j
+        let n = self.degree as usize;
+        let mut acc = Complex { re: 1., im: 0. };
+        let mut zp = Complex { re: 1., im: 0. };
+        for j in (1..n).rev() {
+            zp = cmul(zp, z);
+            acc = cadd(
+                cmul(acc, d),
+                Complex {
+                    re: self.binomial[j] * zp.re,
+                    im: self.binomial[j] * zp.im,
+                },
+            );
+        }
+        return cadd(cmul(acc, d), dc);
+    }
+}
+// escape time for the pixel at offset dc from the reference
+pub fn perturb(reference: &Reference, series: &Series, dc: Complex, maxitr: f64) -> (i32, f64) {
+    let orbit = &reference.orbit;
+    let mut d = approximate(&series.coefficients, dc);
+    let mut m = series.skip;
+    let mut iterations: i32 = series.skip as i32;
+    while iterations < maxitr as i32 {
+        let z = cadd(orbit[m], d);
+        let r = norm(z);
+        if r >= 4. {
+            return (iterations, r);
+        }
+        // glitch: once the pixel passes closer to 0 than to the reference, d has lost
+        // its precision relative to Z, so rebase onto the start of the orbit (Z = 0)
+        // the same happens when the reference itself escaped before maxitr
+        if r < norm(d) || m + 1 == orbit.len() {
+            d = z;
+            m = 0;
+        }
+        d = reference.delta(orbit[m], d, dc);
+        m += 1;
+        iterations += 1;
+    }
+    return (iterations, norm(cadd(orbit[m], d)));
+}
+
+// series approximation: for z^2 + c every pixel's offset is d_k ~ A_k dc + B_k dc^2 + C_k dc^3
+// with coefficients shared by the whole frame, so all pixels can start at iteration k
+// instead of 0, as long as the truncated terms stay negligible across the view
+const SERIES_TOLERANCE: f64 = 1e-6;
+pub struct Series {
+    pub skip: usize,
+    coefficients: [Complex; 3],
+}
+impl Series {
+    pub fn none() -> Series {
+        Series {
+            skip: 0,
+            coefficients: [Complex { re: 0., im: 0. }; 3],
+        }
+    }
+    // probes are offsets at the edges of the view, radius the largest |dc| in it
+    pub fn new(reference: &Reference, probes: &[Complex], radius: f64) -> Series {
+        if reference.degree != 2 {
+            return Series::none();
+        }
+        let orbit = &reference.orbit;
+        let zero = Complex { re: 0., im: 0. };
+        let mut terms = vec![[zero; 3]];
+        // A' = 2ZA + 1, B' = 2ZB + A^2, C' = 2ZC + 2AB
+        for z in &orbit[..orbit.len() - 1] {
+            let [a, b, c] = *terms.last().unwrap();
+            let twoz = Complex {
+                re: 2. * z.re,
+                im: 2. * z.im,
+            };
+            let next = [
+                cadd(cmul(twoz, a), Complex { re: 1., im: 0. }),
+                cadd(cmul(twoz, b), cmul(a, a)),
+                cadd(cmul(twoz, c), cmul(Complex { re: 2. * a.re, im: 2. * a.im }, b)),
+            ];
+            // stop once the cubic term is no longer tiny next to the quadratic one
+            if norm(next[2]).sqrt() * radius > SERIES_TOLERANCE * norm(next[1]).sqrt() {
+                break;
+            }
+            terms.push(next);
+        }
+        let mut skip = terms.len() - 1;
+        // bound the error by checking the series against real perturbed orbits at the probes
+        for dc in probes {
+            let mut d = zero;
+            for (k, term) in terms.iter().enumerate().take(skip + 1).skip(1) {
+                d = reference.delta(orbit[k - 1], d, *dc);
+                let z = cadd(orbit[k], d);
+                let error = norm(csub(approximate(term, *dc), d)).sqrt();
+                if norm(z) < norm(d) || error > SERIES_TOLERANCE * norm(d).sqrt() {
+                    skip = k - 1;
+                    break;
+                }
+            }
+        }
+        Series {
+            skip,
+            coefficients: terms[skip],
+        }
+    }
+}
+fn approximate(terms: &[Complex; 3], dc: Complex) -> Complex {
+    let dc2 = cmul(dc, dc);
+    cadd(
+        cadd(cmul(terms[0], dc), cmul(terms[1], dc2)),
+        cmul(terms[2], cmul(dc2, dc)),
+    )
+}
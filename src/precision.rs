@@ -0,0 +1,326 @@
+use crate::fractal::Coord;
+use num_bigint::{BigInt, Sign};
+
+// fixed point reals, value = mantissa / 2^bits, for computing reference orbits past f64
+#[derive(Clone, Debug, PartialEq)]
+pub struct Real {
+    mantissa: BigInt,
+    bits: u32,
+}
+impl Real {
+    pub fn zero(bits: u32) -> Real {
+        Real {
+            mantissa: BigInt::from(0),
+            bits,
+        }
+    }
+    pub fn to_f64(&self) -> f64 {
+        let shift = self.mantissa.bits().saturating_sub(64);
+        let (sign, digits) = (&self.mantissa >> shift).to_u64_digits();
+        let top = digits.first().copied().unwrap_or(0) as f64;
+        let value = top * 2f64.powi(shift as i32 - self.bits as i32);
+        if sign == Sign::Minus {
+            return -value;
+        }
+        value
+    }
+    pub fn add(&self, other: &Real) -> Real {
+        Real {
+            mantissa: &self.mantissa + &other.mantissa,
+            bits: self.bits,
+        }
+    }
+    pub fn sub(&self, other: &Real) -> Real {
+        Real {
+            mantissa: &self.mantissa - &other.mantissa,
+            bits: self.bits,
+        }
+    }
+    pub fn mul(&self, other: &Real) -> Real {
+        Real {
+            mantissa: (&self.mantissa * &other.mantissa) >> self.bits as u64,
+            bits: self.bits,
+        }
+    }
+    pub fn mul_int(&self, n: i64) -> Real {
+        Real {
+            mantissa: &self.mantissa * n,
+            bits: self.bits,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigComplex {
+    pub re: Real,
+    pub im: Real,
+}
+impl BigComplex {
+    pub fn add(&self, other: &BigComplex) -> BigComplex {
+        BigComplex {
+            re: self.re.add(&other.re),
+            im: self.im.add(&other.im),
+        }
+    }
+    pub fn mul(&self, other: &BigComplex) -> BigComplex {
+        BigComplex {
+            re: self.re.mul(&other.re).sub(&self.im.mul(&other.im)),
+            im: self.re.mul(&other.im).add(&self.im.mul(&other.re)),
+        }
+    }
+    pub fn square(&self) -> BigComplex {
+        BigComplex {
+            re: self.re.mul(&self.re).sub(&self.im.mul(&self.im)),
+            im: self.re.mul(&self.im).mul_int(2),
+        }
+    }
+    pub fn powi(&self, n: i32) -> BigComplex {
+        if n == 1 {
+            return self.clone();
+        }
+        if n % 2 == 0 {
+            return self.square().powi(n / 2);
+        }
+        return self.mul(&self.square().powi((n - 1) / 2));
+    }
+}
+
+// x = m * 2^e with m a 53 bit integer, straight from the bit pattern
+fn split(x: f64) -> (i64, i64) {
+    let raw = x.to_bits();
+    let exponent = ((raw >> 52) & 0x7ff) as i64;
+    let fraction = (raw & ((1 << 52) - 1)) as i64;
+    let (m, e) = if exponent == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1 << 52), exponent - 1075)
+    };
+    if x < 0. {
+        return (-m, e);
+    }
+    (m, e)
+}
+
+// exact decimal fixed point, value = digits / 10^scale, so typed or pasted coordinates
+// print back exactly as they went in
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decimal {
+    digits: BigInt,
+    scale: u32,
+}
+impl Decimal {
+    pub fn zero() -> Decimal {
+        Decimal {
+            digits: BigInt::from(0),
+            scale: 0,
+        }
+    }
+    // rounded to the given number of decimal places
+    pub fn from_f64(x: f64, places: u32) -> Decimal {
+        if x == 0. || !x.is_finite() {
+            return Decimal::zero();
+        }
+        let (m, e) = split(x);
+        let digits = BigInt::from(m) * BigInt::from(10).pow(places);
+        let digits = if e >= 0 {
+            digits << e as u64
+        } else {
+            // round half up before shifting the binary fraction away
+            (digits + (BigInt::from(1) << (-e - 1) as u64)) >> (-e) as u64
+        };
+        Decimal {
+            digits,
+            scale: places,
+        }
+    }
+    fn rescale(&self, scale: u32) -> BigInt {
+        &self.digits * BigInt::from(10).pow(scale - self.scale)
+    }
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        Decimal {
+            digits: self.rescale(scale) + other.rescale(scale),
+            scale,
+        }
+    }
+    pub fn neg(&self) -> Decimal {
+        Decimal {
+            digits: -&self.digits,
+            scale: self.scale,
+        }
+    }
+    pub fn to_f64(&self) -> f64 {
+        // the std parser rounds arbitrarily long decimals correctly
+        format!("{}e-{}", self.digits, self.scale)
+            .parse()
+            .unwrap_or(0.)
+    }
+    pub fn to_real(&self, bits: u32) -> Real {
+        Real {
+            mantissa: (&self.digits << bits as u64) / BigInt::from(10).pow(self.scale),
+            bits,
+        }
+    }
+}
+impl std::str::FromStr for Decimal {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Decimal, String> {
+        let s = s.trim();
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, "0"),
+        };
+        let exponent: i64 = exponent
+            .parse()
+            .map_err(|_| format!("bad exponent `{}`", exponent))?;
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let all = format!("{}{}", whole, fraction);
+        if all.is_empty() || !all.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("`{}` is not a number", s));
+        }
+        let mut digits: BigInt = all.parse().map_err(|_| format!("`{}` is not a number", s))?;
+        if negative {
+            digits = -digits;
+        }
+        let scale = (fraction.len() as i64)
+            .checked_sub(exponent)
+            .filter(|scale| scale.abs() <= 100000)
+            .ok_or(format!("exponent of `{}` is out of range", s))?;
+        if scale < 0 {
+            return Ok(Decimal {
+                digits: digits * BigInt::from(10).pow((-scale) as u32),
+                scale: 0,
+            });
+        }
+        Ok(Decimal {
+            digits,
+            scale: scale as u32,
+        })
+    }
+}
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (sign, magnitude) = match self.digits.sign() {
+            Sign::Minus => ("-", (-&self.digits).to_string()),
+            _ => ("", self.digits.to_string()),
+        };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, magnitude);
+        }
+        let padded = format!("{:0>width$}", magnitude, width = scale + 1);
+        let (whole, fraction) = padded.split_at(padded.len() - scale);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            return write!(f, "{}{}", sign, whole);
+        }
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+// the view center, kept exact; rendering only ever sees f64 offsets from it
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigCoord {
+    pub x: Decimal,
+    pub y: Decimal,
+}
+impl BigCoord {
+    // from the shortest decimal that round-trips, so -0.765 stays -0.765
+    pub fn new(x: f64, y: f64) -> BigCoord {
+        BigCoord {
+            x: x.to_string().parse().unwrap_or(Decimal::zero()),
+            y: y.to_string().parse().unwrap_or(Decimal::zero()),
+        }
+    }
+    pub fn to_coord(&self) -> Coord {
+        Coord {
+            x: self.x.to_f64(),
+            y: self.y.to_f64(),
+        }
+    }
+    // move by an f64 offset, kept to a few digits finer than the pixels at this zoom
+    pub fn offset(&self, dx: f64, dy: f64, zoom: f64) -> BigCoord {
+        let places = ((-zoom.log10()).ceil() + 6.).max(0.) as u32;
+        BigCoord {
+            x: self.x.add(&Decimal::from_f64(dx, places)),
+            y: self.y.add(&Decimal::from_f64(dy, places)),
+        }
+    }
+    // exact difference, only rounded to f64 at the end
+    pub fn difference(&self, other: &BigCoord) -> Coord {
+        Coord {
+            x: self.x.add(&other.x.neg()).to_f64(),
+            y: self.y.add(&other.y.neg()).to_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(s: &str) -> Decimal {
+        return s.parse().unwrap();
+    }
+
+    #[test]
+    fn typed_coordinates_print_back_exactly() {
+        for s in [
+            "0",
+            "-0.765",
+            "1.25",
+            "-2",
+            "0.000000000000000000000000000000000000000001",
+            "-1.7490930559866024966183810935742187500000001",
+            "-0.74364388703715870475219150611477392",
+            "123456789012345678901234567890.0987654321",
+        ] {
+            assert_eq!(decimal(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn other_spellings() {
+        for (s, printed) in [
+            ("+1.5", "1.5"),
+            ("1.500", "1.5"),
+            (".5", "0.5"),
+            ("5.", "5"),
+            ("-0", "0"),
+            (" 2 ", "2"),
+            ("1e3", "1000"),
+            ("1.5E-3", "0.0015"),
+            ("-25e-1", "-2.5"),
+        ] {
+            assert_eq!(decimal(s).to_string(), printed, "{}", s);
+            // and what's printed reads back as the same value
+            assert_eq!(decimal(printed).to_string(), printed);
+        }
+    }
+
+    #[test]
+    fn not_numbers() {
+        for s in ["", "-", ".", "1.2.3", "abc", "1e", "1e5.5", "0x10", "1,5", "--1"] {
+            assert!(s.parse::<Decimal>().is_err(), "{}", s);
+        }
+        assert!("1e1000000".parse::<Decimal>().is_err());
+        assert!("1e-9223372036854775808".parse::<Decimal>().is_err());
+        assert!("1.5e9223372036854775807".parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn arithmetic_stays_exact() {
+        let sum = decimal("0.1").add(&decimal("0.2"));
+        assert_eq!(sum.to_string(), "0.3");
+        let tiny = decimal("-0.7436438870371587047521915061147739").add(&decimal("1e-40"));
+        assert_eq!(tiny.to_string(), "-0.7436438870371587047521915061147738999999");
+        assert_eq!(decimal("1.5").neg().to_string(), "-1.5");
+        assert_eq!(decimal("-0.765").to_f64(), -0.765);
+        assert_eq!(Decimal::from_f64(0.1, 5).to_string(), "0.1");
+        assert_eq!(Decimal::from_f64(-1. / 3., 4).to_string(), "-0.3333");
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+
+// sessions are a small subset of toml: `version = 1` first, then one `key = "value"` per line,
+// every value a string so each field parses the same way it does from an exported image
+// bump this when a field changes meaning, older versions are still read
+pub const VERSION: u32 = 1;
+
+// the explorer's own directory in the user's config
+pub fn config() -> Option<PathBuf> {
+    let config = std::env::var_os("APPDATA")
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    return Some(config.join("multibrot-explorer"));
+}
+// where the session is saved on exit and restored from on startup
+pub fn last() -> Option<PathBuf> {
+    return Some(config()?.join("last-session.toml"));
+}
+
+pub fn write(path: &Path, fields: &[(String, String)]) -> Result<(), String> {
+    let mut text = format!("version = {}\n", VERSION);
+    for (key, value) in fields {
+        text += &format!("{} = {}\n", key, quote(value));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+    }
+    return std::fs::write(path, text).map_err(|e| format!("can't write {}: {}", path.display(), e));
+}
+
+pub fn read(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let mut fields = Vec::new();
+    let mut version = None;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let bad = || format!("{} line {}: expected key = \"value\"", path.display(), n + 1);
+        let (key, value) = line.split_once('=').ok_or_else(bad)?;
+        let (key, value) = (key.trim(), value.trim());
+        if key == "version" {
+            version = value.parse::<u32>().ok();
+            continue;
+        }
+        fields.push((key.to_string(), unquote(value).ok_or_else(bad)?));
+    }
+    match version {
+        Some(version) if version <= VERSION => return Ok(fields),
+        Some(version) => return Err(format!("{} is from a newer version ({}) of the explorer", path.display(), version)),
+        None => return Err(format!("{} is not a session file (no version)", path.display())),
+    }
+}
+
+// a toml basic string
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\t' => quoted += "\\t",
+            c if c.is_control() => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+// the inverse of quote, None if it isn't one
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return None;
+        }
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '"' => unquoted.push('"'),
+            '\\' => unquoted.push('\\'),
+            'n' => unquoted.push('\n'),
+            't' => unquoted.push('\t'),
+            'u' => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+                unquoted.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            _ => return None,
+        }
+    }
+    return Some(unquoted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempFile;
+
+    #[test]
+    fn toml_strings() {
+        let value = "say \"hi\"\\ on\ntwo lines\u{1} ∞";
+        assert_eq!(quote(value), r#""say \"hi\"\\ on\ntwo lines\u0001 ∞""#);
+        assert_eq!(unquote(&quote(value)).as_deref(), Some(value));
+        for bad in [r#""in"side""#, r#""\q""#, r#""\u12""#, r#""\u+123""#, r#""end\""#] {
+            assert_eq!(unquote(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let file = TempFile::new("session.toml");
+        let fields = vec![(String::from("step"), String::from("z^2 + c"))];
+        write(&file.0, &fields).unwrap();
+        assert_eq!(read(&file.0), Ok(fields));
+        std::fs::write(&file.0, format!("version = {}\n", VERSION + 1)).unwrap();
+        assert!(read(&file.0).unwrap_err().contains("newer version"));
+        std::fs::write(&file.0, "key = \"value\"\n").unwrap();
+        assert!(read(&file.0).unwrap_err().contains("no version"));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::fractal::{mandel2, mandel2f};
+
+// mandel2 for several pixels at once, one per vector lane, lanes drop out as they escape
+// uses plain multiplies and adds in the same order as mandel2 (no fused multiply-add),
+// so every lane gets exactly the scalar result
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Simd {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+impl Simd {
+    pub const ALL: [Simd; 3] = [Simd::Scalar, Simd::Sse2, Simd::Avx2];
+    pub fn output(&self) -> String {
+        match self {
+            Simd::Scalar => return String::from("scalar"),
+            Simd::Sse2 => return String::from("SSE2 (2 lanes, 4 in f32)"),
+            Simd::Avx2 => return String::from("AVX2 (4 lanes, 8 in f32)"),
+        }
+    }
+    // whether this cpu can run it
+    pub fn available(&self) -> bool {
+        match self {
+            Simd::Scalar => return true,
+            #[cfg(target_arch = "x86_64")]
+            Simd::Sse2 => return is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Simd::Avx2 => return is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => return false,
+        }
+    }
+    pub fn best() -> Simd {
+        return *Simd::ALL.iter().rev().find(|simd| simd.available()).unwrap();
+    }
+    // how many pixels one vector holds, twice as many in f32
+    fn lanes(&self, single: bool) -> usize {
+        let lanes = match self {
+            _ if !self.available() => 1,
+            Simd::Scalar => 1,
+            Simd::Sse2 => 2,
+            Simd::Avx2 => 4,
+        };
+        if single && lanes > 1 {
+            return lanes * 2;
+        }
+        return lanes;
+    }
+}
+
+// (iterations, r) for every (x0[i], y0[i]), same as calling mandel2 on each
+pub fn mandel2x(simd: Simd, x0: &[f64], y0: &[f64], maxitr: f64) -> Vec<(i32, f64)> {
+    let lanes = simd.lanes(false);
+    let mut out = Vec::with_capacity(x0.len());
+    let whole = if lanes > 1 { x0.len() / lanes * lanes } else { 0 };
+    if whole > 0 {
+        for (x, y) in x0[..whole].chunks(lanes).zip(y0[..whole].chunks(lanes)) {
+            // safe: the feature was detected above
+            #[cfg(target_arch = "x86_64")]
+            unsafe {
+                match simd {
+                    Simd::Avx2 => out.extend(avx2(x, y, maxitr)),
+                    _ => out.extend(sse2(x, y, maxitr)),
+                }
+            }
+        }
+    }
+    // whatever doesn't fill a whole vector
+    for (x, y) in x0[whole..].iter().zip(&y0[whole..]) {
+        out.push(mandel2(*x, *y, maxitr));
+    }
+    return out;
+}
+// the same in f32, same as calling mandel2f on each
+pub fn mandel2x32(simd: Simd, x0: &[f32], y0: &[f32], maxitr: f64) -> Vec<(i32, f64)> {
+    let lanes = simd.lanes(true);
+    let mut out = Vec::with_capacity(x0.len());
+    let whole = if lanes > 1 { x0.len() / lanes * lanes } else { 0 };
+    if whole > 0 {
+        for (x, y) in x0[..whole].chunks(lanes).zip(y0[..whole].chunks(lanes)) {
+            #[cfg(target_arch = "x86_64")]
+            unsafe {
+                match simd {
+                    Simd::Avx2 => out.extend(avx2_f32(x, y, maxitr)),
+                    _ => out.extend(sse2_f32(x, y, maxitr)),
+                }
+            }
+        }
+    }
+    for (x, y) in x0[whole..].iter().zip(&y0[whole..]) {
+        out.push(mandel2f(*x, *y, maxitr));
+    }
+    return out;
+}
+
+// one vector kernel per instruction set and float width, all the same loop as mandel2
+macro_rules! kernel {
+    (
+        $name:ident, $feature:literal, $float:ty, $lanes:literal,
+        $load:path, $store:path, $splat:path, $zero:path,
+        $add:path, $sub:path, $mul:path, $and:path, $lt:path, $eq:path, $mask:path
+    ) => {
+        #[cfg(target_arch = "x86_64")]
+        #[target_feature(enable = $feature)]
+        unsafe fn $name(x0: &[$float], y0: &[$float], maxitr: f64) -> [(i32, f64); $lanes] {
+            use std::arch::x86_64::*;
+            let max = maxitr as i32;
+            let cx = $load(x0.as_ptr());
+            let cy = $load(y0.as_ptr());
+            let four = $splat(4.);
+            let mut x = $zero();
+            let mut y = $zero();
+            let mut x2 = $zero();
+            let mut y2 = $zero();
+            let mut xold = $zero();
+            let mut yold = $zero();
+            let mut result = [(0, 0.); $lanes];
+            let mut r: [$float; $lanes] = [0.; $lanes];
+            // bit i set while lane i is still iterating
+            let mut active: i32 = (1 << $lanes) - 1;
+            let mut iterations = 0;
+            loop {
+                let sum = $add(x2, y2);
+                let inside = $mask($lt(sum, four));
+                let leaving = if iterations < max { active & !inside } else { active };
+                if leaving != 0 {
+                    $store(r.as_mut_ptr(), sum);
+                    for lane in 0..$lanes {
+                        if leaving & (1 << lane) != 0 {
+                            result[lane] = (iterations, r[lane] as f64);
+                        }
+                    }
+                    active &= !leaving;
+                    if active == 0 {
+                        return result;
+                    }
+                }
+                y = $add($mul(y, $add(x, x)), cy);
+                x = $add($sub(x2, y2), cx);
+                x2 = $mul(x, x);
+                y2 = $mul(y, y);
+                let periodic = $mask($and($eq(x, xold), $eq(y, yold))) & active;
+                if periodic != 0 {
+                    $store(r.as_mut_ptr(), $add(x2, y2));
+                    for lane in 0..$lanes {
+                        if periodic & (1 << lane) != 0 {
+                            result[lane] = (max, r[lane] as f64);
+                        }
+                    }
+                    active &= !periodic;
+                    if active == 0 {
+                        return result;
+                    }
+                }
+                if iterations % 25 == 0 {
+                    xold = x;
+                    yold = y;
+                }
+                iterations += 1;
+            }
+        }
+    };
+}
+kernel!(
+    avx2, "avx2", f64, 4,
+    _mm256_loadu_pd, _mm256_storeu_pd, _mm256_set1_pd, _mm256_setzero_pd,
+    _mm256_add_pd, _mm256_sub_pd, _mm256_mul_pd, _mm256_and_pd,
+    _mm256_cmp_pd::<_CMP_LT_OQ>, _mm256_cmp_pd::<_CMP_EQ_OQ>, _mm256_movemask_pd
+);
+kernel!(
+    sse2, "sse2", f64, 2,
+    _mm_loadu_pd, _mm_storeu_pd, _mm_set1_pd, _mm_setzero_pd,
+    _mm_add_pd, _mm_sub_pd, _mm_mul_pd, _mm_and_pd,
+    _mm_cmplt_pd, _mm_cmpeq_pd, _mm_movemask_pd
+);
+kernel!(
+    avx2_f32, "avx2", f32, 8,
+    _mm256_loadu_ps, _mm256_storeu_ps, _mm256_set1_ps, _mm256_setzero_ps,
+    _mm256_add_ps, _mm256_sub_ps, _mm256_mul_ps, _mm256_and_ps,
+    _mm256_cmp_ps::<_CMP_LT_OQ>, _mm256_cmp_ps::<_CMP_EQ_OQ>, _mm256_movemask_ps
+);
+kernel!(
+    sse2_f32, "sse2", f32, 4,
+    _mm_loadu_ps, _mm_storeu_ps, _mm_set1_ps, _mm_setzero_ps,
+    _mm_add_ps, _mm_sub_ps, _mm_mul_ps, _mm_and_ps,
+    _mm_cmplt_ps, _mm_cmpeq_ps, _mm_movemask_ps
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a grid over the whole set plus a strip along the boundary, where orbits are long
+    fn points() -> (Vec<f64>, Vec<f64>) {
+        let (mut x0, mut y0) = (Vec::new(), Vec::new());
+        for i in 0..101 {
+            for j in 0..91 {
+                x0.push(-2.2 + 2.7 * i as f64 / 100.);
+                y0.push(-1.2 + 2.4 * j as f64 / 90.);
+            }
+        }
+        for i in 0..1001 {
+            x0.push(-0.75 + 1e-4 * i as f64);
+            y0.push(0.1 + 1e-5 * i as f64);
+        }
+        return (x0, y0);
+    }
+
+    fn check(simd: Simd, maxitr: f64) {
+        let (x0, y0) = points();
+        let vector = mandel2x(simd, &x0, &y0, maxitr);
+        assert_eq!(vector.len(), x0.len());
+        for (i, (iterations, r)) in vector.into_iter().enumerate() {
+            let (expected, er) = mandel2(x0[i], y0[i], maxitr);
+            assert_eq!(iterations, expected, "{:?} at ({}, {})", simd, x0[i], y0[i]);
+            assert_eq!(r.to_bits(), er.to_bits(), "{:?} at ({}, {})", simd, x0[i], y0[i]);
+        }
+        let (x0, y0): (Vec<f32>, Vec<f32>) = (
+            x0.iter().map(|x| *x as f32).collect(),
+            y0.iter().map(|y| *y as f32).collect(),
+        );
+        let vector = mandel2x32(simd, &x0, &y0, maxitr);
+        assert_eq!(vector.len(), x0.len());
+        for (i, (iterations, r)) in vector.into_iter().enumerate() {
+            let (expected, er) = mandel2f(x0[i], y0[i], maxitr);
+            assert_eq!(iterations, expected, "{:?} f32 at ({}, {})", simd, x0[i], y0[i]);
+            assert_eq!(r.to_bits(), er.to_bits(), "{:?} f32 at ({}, {})", simd, x0[i], y0[i]);
+        }
+    }
+
+    #[test]
+    fn identical_to_scalar() {
+        for simd in Simd::ALL {
+            if simd.available() {
+                check(simd, 1000.);
+                check(simd, 25.);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_iterations() {
+        for simd in Simd::ALL {
+            if simd.available() {
+                check(simd, 0.);
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::fractal::{color, Plane, Progress, Sample};
+use crate::ColoringMode;
+use eframe::egui::ecolor::{gamma_u8_from_linear_f32, linear_f32_from_gamma_u8};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+use std::sync::atomic::Ordering;
+
+// how the samples inside one pixel are laid out
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern {
+    // just the one in the middle of the pixel
+    Off,
+    // n x n evenly spaced
+    Grid,
+    // one random point in each cell of the n x n grid, the same ones every frame
+    Jittered,
+    // the n x n grid sheared so no two samples share a row or column, better on near
+    // horizontal and vertical edges
+    RotatedGrid,
+}
+impl Pattern {
+    pub const ALL: [Pattern; 4] = [Pattern::Off, Pattern::Grid, Pattern::Jittered, Pattern::RotatedGrid];
+    pub fn output(&self) -> String {
+        match self {
+            Pattern::Off => return String::from("off"),
+            Pattern::Grid => return String::from("grid"),
+            Pattern::Jittered => return String::from("jittered"),
+            Pattern::RotatedGrid => return String::from("rotated grid"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Supersampling {
+    pub pattern: Pattern,
+    // samples per side, n * n per pixel
+    pub n: usize,
+    // only pixels whose neighbours differ by more than threshold (in smoothed iterations)
+    pub adaptive: bool,
+    pub threshold: f64,
+}
+impl Supersampling {
+    pub const OFF: Supersampling = Supersampling {
+        pattern: Pattern::Off,
+        n: 3,
+        adaptive: false,
+        threshold: 1.,
+    };
+    pub fn enabled(&self) -> bool {
+        return self.pattern != Pattern::Off;
+    }
+    // where pixel (x, y)'s samples sit inside it, each within [0, 1) x [0, 1)
+    pub fn points(&self, x: usize, y: usize) -> Vec<(f64, f64)> {
+        let n = self.n;
+        let cell = 1. / n as f64;
+        return (0..n * n)
+            .map(|k| {
+                let (i, j) = ((k % n) as f64, (k / n) as f64);
+                match self.pattern {
+                    Pattern::Off => (0.5, 0.5),
+                    Pattern::Grid => ((i + 0.5) * cell, (j + 0.5) * cell),
+                    Pattern::Jittered => {
+                        let random = hash(x, y, k);
+                        ((i + unit(random)) * cell, (j + unit(random >> 32)) * cell)
+                    }
+                    Pattern::RotatedGrid => {
+                        let (x, y) = (i * n as f64 + j, j * n as f64 + (n - 1) as f64 - i);
+                        ((x + 0.5) * cell * cell, (y + 0.5) * cell * cell)
+                    }
+                }
+            })
+            .collect();
+    }
+}
+
+// splitmix64 of the pixel and sample index, so the jitter doesn't change between frames
+fn hash(x: usize, y: usize, k: usize) -> u64 {
+    let mut z = (x as u64) ^ (y as u64) << 24 ^ (k as u64) << 48;
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return z ^ (z >> 31);
+}
+// the low 32 bits as a number in [0, 1)
+fn unit(random: u64) -> f64 {
+    return (random & 0xffff_ffff) as f64 / 4294967296.;
+}
+
+// the continuous iteration count the colourings use, None inside the set
+fn smooth(sample: Sample, maxitr: f64) -> Option<f64> {
+    if sample.iterations >= maxitr as i32 {
+        return None;
+    }
+    return Some(sample.iterations as f64 + 1. - (sample.r.ln() / 2.).ln() / 2_f64.ln());
+}
+// whether pixel (x, y) differs enough from one of its four neighbours to be worth supersampling
+fn edge(samples: &[Sample], width: usize, x: usize, y: usize, maxitr: f64, threshold: f64) -> bool {
+    let height = samples.len() / width;
+    let here = smooth(samples[y * width + x], maxitr);
+    let neighbours = [
+        (x > 0).then(|| y * width + x - 1),
+        (x + 1 < width).then(|| y * width + x + 1),
+        (y > 0).then(|| (y - 1) * width + x),
+        (y + 1 < height).then(|| (y + 1) * width + x),
+    ];
+    return neighbours.iter().flatten().any(|i| match (here, smooth(samples[*i], maxitr)) {
+        (None, None) => false,
+        // NaN (from odd escape radii) counts as different
+        (Some(a), Some(b)) => (a - b).is_nan() || (a - b).abs() > threshold,
+        _ => true,
+    });
+}
+
+// the pass after every pixel has its centre sample: computes the subsamples of each pixel that
+// needs them and doesn't have them yet (carried over from an earlier frame)
+pub fn supersample(
+    plane: &Plane,
+    samples: &[Sample],
+    subsamples: &mut [Vec<Sample>],
+    supersampling: Supersampling,
+    progress: &Progress,
+) -> Option<()> {
+    let width = plane.width as usize;
+    let per = supersampling.n * supersampling.n;
+    subsamples
+        .par_chunks_mut(width)
+        .enumerate()
+        .map(|(y, row)| {
+            let xs: Vec<usize> = (0..width)
+                .filter(|x| row[*x].is_empty())
+                .filter(|x| {
+                    !supersampling.adaptive
+                        || edge(samples, width, *x, y, plane.maxitr, supersampling.threshold)
+                })
+                .collect();
+            for xs in xs.chunks(16) {
+                if progress.cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let points: Vec<(f64, f64)> = xs
+                    .iter()
+                    .flat_map(|x| {
+                        let points = supersampling.points(*x, y);
+                        points.into_iter().map(|(dx, dy)| (*x as f64 + dx, y as f64 + dy))
+                    })
+                    .collect();
+                for (x, samples) in xs.iter().zip(plane.sample_points(&points).chunks(per)) {
+                    row[*x] = samples.to_vec();
+                }
+            }
+            progress.lines.fetch_add(1, Ordering::Relaxed);
+            Some(())
+        })
+        .collect()
+}
+
+// the colours of a pixel's samples averaged in linear light, averaging the srgb bytes
+// directly would darken every edge
+pub fn average(samples: &[Sample], maxitr: f64, mode: ColoringMode) -> [u8; 4] {
+    let mut sum = [0.; 3];
+    for sample in samples {
+        let rgba = color(*sample, maxitr, mode);
+        for channel in 0..3 {
+            sum[channel] += linear_f32_from_gamma_u8(rgba[channel]);
+        }
+    }
+    let n = samples.len() as f32;
+    return [
+        gamma_u8_from_linear_f32(sum[0] / n),
+        gamma_u8_from_linear_f32(sum[1] / n),
+        gamma_u8_from_linear_f32(sum[2] / n),
+        255,
+    ];
+}
@@ -0,0 +1,83 @@
+use crate::fractal::Coord;
+use crate::precision::BigCoord;
+
+// half the height of the view at scale 1, which fits the whole mandelbrot set
+const EXTENT: f64 = 1.12;
+
+// which part of the plane is on screen: the middle of the image sits on center, pixels are
+// square and 2 * EXTENT * scale / height wide, and the grid is turned by rotation (radians)
+// pixel coordinates are continuous, pixel (i, j) covers [i, i + 1) x [j, j + 1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub center: BigCoord,
+    pub scale: f64,
+    pub rotation: f64,
+    pub width: i32,
+    pub height: i32,
+}
+impl Viewport {
+    pub fn new(center: BigCoord, scale: f64, width: i32, height: i32) -> Viewport {
+        Viewport {
+            center,
+            scale,
+            rotation: 0.,
+            width,
+            height,
+        }
+    }
+    // the side of one pixel in the plane
+    pub fn pixel(&self) -> f64 {
+        2. * EXTENT * self.scale / self.height as f64
+    }
+    // from the center to the screen position (x, y), in the plane
+    pub fn offset(&self, x: f64, y: f64) -> Coord {
+        let pixel = self.pixel();
+        let dx = (x - self.width as f64 / 2.) * pixel;
+        let dy = (y - self.height as f64 / 2.) * pixel;
+        let (sin, cos) = self.rotation.sin_cos();
+        Coord {
+            x: dx * cos - dy * sin,
+            y: dx * sin + dy * cos,
+        }
+    }
+    // the inverse of offset, where a point that far from the center lands on screen
+    pub fn screen(&self, offset: Coord) -> (f64, f64) {
+        let pixel = self.pixel();
+        let (sin, cos) = self.rotation.sin_cos();
+        let dx = offset.x * cos + offset.y * sin;
+        let dy = -offset.x * sin + offset.y * cos;
+        (dx / pixel + self.width as f64 / 2., dy / pixel + self.height as f64 / 2.)
+    }
+    // the exact point under the screen position
+    pub fn at(&self, x: f64, y: f64) -> BigCoord {
+        let offset = self.offset(x, y);
+        self.center.offset(offset.x, offset.y, self.scale)
+    }
+    // the center of pixel (x, y), where it gets sampled
+    pub fn sample(&self, x: usize, y: usize) -> Coord {
+        self.offset(x as f64 + 0.5, y as f64 + 0.5)
+    }
+    // turn by angle (radians) about the middle of the view, kept within a half turn either way
+    pub fn rotate(&mut self, angle: f64) {
+        let turn = std::f64::consts::TAU;
+        self.rotation = (self.rotation + angle + turn / 2.).rem_euclid(turn) - turn / 2.;
+    }
+    // the width x height pixels starting at (x, y) as a view of their own, same pixel size and angle
+    pub fn crop(&self, x: i32, y: i32, width: i32, height: i32) -> Viewport {
+        Viewport {
+            center: self.at(x as f64 + width as f64 / 2., y as f64 + height as f64 / 2.),
+            scale: self.scale * height as f64 / self.height as f64,
+            rotation: self.rotation,
+            width,
+            height,
+        }
+    }
+    // move by a whole number of pixels along the screen axes, so earlier samples still line up
+    pub fn pan(&mut self, x: f64, y: f64) {
+        let offset = self.offset(
+            x.round() + self.width as f64 / 2.,
+            y.round() + self.height as f64 / 2.,
+        );
+        self.center = self.center.offset(offset.x, offset.y, self.scale);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::fill::{subdivide, Fill};
+use crate::formula::Formula;
+use crate::fractal::{refine, Coord, Plane, Progress, Sample, Stats, PASSES};
+use crate::simd::Simd;
+use crate::supersample::{supersample, Supersampling};
+use crate::viewport::Viewport;
+use eframe::egui;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::time::Instant;
+
+// a finished set of samples and the view they were computed for
+pub struct Frame {
+    pub viewport: Viewport,
+    pub maxitr: f64,
+    pub samples: Vec<Sample>,
+    // per pixel, empty for the ones that weren't supersampled (and in every pass before the last)
+    pub subsamples: Vec<Vec<Sample>>,
+    pub stats: Stats,
+    pub time: f64,
+    // the pass this came from, 1 once every pixel is computed
+    pub step: usize,
+    // nothing more is coming after this one
+    pub complete: bool,
+}
+
+// samples and subsamples carried over from the last frame, and which pixels they cover
+pub type Reuse = (Vec<Sample>, Vec<Vec<Sample>>, Vec<bool>);
+
+// one frame rendering on a background thread, so the ui keeps running meanwhile
+// every pass is sent back as it finishes, dropping the job cancels it
+pub struct Job {
+    progress: Arc<Progress>,
+    receiver: Receiver<Frame>,
+    lines: usize,
+}
+impl Job {
+    pub fn spawn(
+        viewport: Viewport,
+        maxitr: f64,
+        formula: Box<dyn Formula>,
+        julia: Option<Coord>,
+        reuse: Option<Reuse>,
+        fill: Fill,
+        simd: Simd,
+        supersampling: Supersampling,
+        ctx: egui::Context,
+    ) -> Job {
+        let height = viewport.height;
+        let progress = Arc::new(Progress::default());
+        let (sender, receiver) = channel();
+        let shared = progress.clone();
+        std::thread::spawn(move || {
+            let now = Instant::now();
+            let Some(plane) = Plane::new(&viewport, maxitr, &*formula, julia, simd, &shared) else {
+                return;
+            };
+            let size = (plane.width * plane.height) as usize;
+            let (mut samples, mut subsamples, known) = reuse.unwrap_or_else(|| {
+                (vec![Sample { iterations: 0, r: 0. }; size], vec![Vec::new(); size], vec![false; size])
+            });
+            let mut stats = plane.stats;
+            let mut done = 0;
+            for step in PASSES {
+                let finished = if step == 1 && fill != Fill::Exact {
+                    subdivide(&plane, &mut samples, &known, done, fill, &shared).map(|(guessed, wrong)| {
+                        stats.guessed = guessed;
+                        stats.wrong = wrong;
+                    })
+                } else {
+                    refine(&plane, &mut samples, &known, step, done, &shared)
+                };
+                if finished.is_none() {
+                    return;
+                }
+                done = step;
+                let complete = step == 1 && !supersampling.enabled();
+                let frame = Frame {
+                    viewport: viewport.clone(),
+                    maxitr,
+                    samples: samples.clone(),
+                    subsamples: Vec::new(),
+                    stats,
+                    time: now.elapsed().as_nanos() as f64,
+                    step,
+                    complete,
+                };
+                // the receiver is gone if the job was replaced in the meantime
+                if sender.send(frame).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            if !supersampling.enabled() {
+                return;
+            }
+            if supersample(&plane, &samples, &mut subsamples, supersampling, &shared).is_none() {
+                return;
+            }
+            let _ = sender.send(Frame {
+                viewport,
+                maxitr,
+                samples,
+                subsamples,
+                stats,
+                time: now.elapsed().as_nanos() as f64,
+                step: 1,
+                complete: true,
+            });
+            ctx.request_repaint();
+        });
+        // the supersampling pass goes over every line once more
+        let extra = if supersampling.enabled() { height as usize } else { 0 };
+        Job {
+            progress,
+            receiver,
+            lines: PASSES.iter().map(|step| (height as usize).div_ceil(*step)).sum::<usize>() + extra,
+        }
+    }
+    // fraction of lines done, over all passes
+    pub fn progress(&self) -> f32 {
+        self.progress.lines.load(Ordering::Relaxed) as f32 / self.lines as f32
+    }
+    // the finest pass that arrived since the last poll
+    pub fn poll(&self) -> Option<Frame> {
+        self.receiver.try_iter().last()
+    }
+}
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+}